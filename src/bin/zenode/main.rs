@@ -0,0 +1,443 @@
+//! `zenode` command-line tool, enabled with the `cli` feature
+//!
+//! ```sh
+//! zenode schema create pet --description "Pet schema" name=str age=int
+//! zenode doc create pet_0020... name=Tuna age=3
+//! zenode doc update pet_0020... <document_id> <view_id> age=4
+//! zenode doc list pet_0020... | jq '.[].fields.name'
+//! ```
+use clap::{Args, Parser, Subcommand};
+use serde_json::{json, Value};
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use zenode::graphql::schemas::FieldDefinition;
+use zenode::{Document, DocumentHandle, FieldTuple, FieldType, FieldValue, KeyStore, Operator};
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Creates schemas and documents on a p2panda node, printing JSON
+#[derive(Parser)]
+#[command(name = "zenode", version)]
+struct Cli {
+    /// GraphQL endpoint of the node, defaults to $ENDPOINT or http://localhost:2020/graphql
+    #[arg(long, global = true)]
+    endpoint: Option<String>,
+
+    /// Key file, defaults to $PRIVATE_KEY or key.txt
+    #[arg(long, global = true)]
+    key: Option<PathBuf>,
+
+    /// Password of an encrypted key file, defaults to $KEY_PASSWORD
+    #[arg(long, global = true)]
+    password: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create and inspect schemas
+    #[command(subcommand)]
+    Schema(SchemaCommand),
+    /// Create, update, delete and fetch documents
+    #[command(subcommand)]
+    Doc(DocCommand),
+    /// Generate and inspect key files
+    #[command(subcommand)]
+    Key(KeyCommand),
+}
+
+#[derive(Subcommand)]
+enum SchemaCommand {
+    /// Create a schema from `name=type` fields, e.g. `age=int`
+    Create {
+        name: String,
+        #[arg(long, default_value = "")]
+        description: String,
+        #[command(flatten)]
+        fields: FieldArgs,
+    },
+    /// Create the schemas of a TOML, YAML or JSON schema file, writing `<path>.lock`
+    Apply { path: PathBuf },
+    /// List the schemas of the node
+    List,
+    /// Show the fields of a schema
+    Show { schema_id: String },
+}
+
+#[derive(Subcommand)]
+enum DocCommand {
+    /// Create a document from `name=value` fields
+    Create {
+        schema_id: String,
+        #[command(flatten)]
+        fields: FieldArgs,
+    },
+    /// Update the document at the respective view id
+    Update {
+        schema_id: String,
+        document_id: String,
+        view_id: String,
+        #[command(flatten)]
+        fields: FieldArgs,
+    },
+    /// Delete the document at the respective view id
+    Delete {
+        schema_id: String,
+        document_id: String,
+        view_id: String,
+    },
+    /// Fetch a document
+    Get {
+        schema_id: String,
+        document_id: String,
+    },
+    /// Fetch all the documents of a schema
+    List { schema_id: String },
+}
+
+#[derive(Subcommand)]
+enum KeyCommand {
+    /// Write a new key file, encrypted if a password is given
+    Generate { path: PathBuf },
+    /// Print the public key of the key file
+    ShowPublic,
+}
+
+#[derive(Args)]
+struct FieldArgs {
+    /// Fields as `name=value`
+    fields: Vec<String>,
+
+    /// Fields as a JSON object, e.g. `{"name":"Tuna","age":3}`
+    #[arg(long)]
+    json: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli).await {
+        Ok(output) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&output).unwrap_or_default()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", json!({ "error": err.to_string() }));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: &Cli) -> CliResult<Value> {
+    let output = match &cli.command {
+        Command::Key(KeyCommand::Generate { path }) => {
+            let key_pair = with_password(KeyStore::create(path), cli).key_pair()?;
+            json!({ "path": path, "public_key": key_pair.public_key().to_string() })
+        }
+        Command::Key(KeyCommand::ShowPublic) => {
+            let key_pair = key_store(cli).key_pair()?;
+            json!({ "public_key": key_pair.public_key().to_string() })
+        }
+        Command::Schema(command) => schema(&operator(cli)?, command).await?,
+        Command::Doc(command) => doc(&operator(cli)?, command).await?,
+    };
+
+    Ok(output)
+}
+
+async fn schema(op: &Operator, command: &SchemaCommand) -> CliResult<Value> {
+    let output = match command {
+        SchemaCommand::Create {
+            name,
+            description,
+            fields,
+        } => {
+            let mut fields = fields.schema_fields()?;
+            let schema_id = op.create_schema(name, description, &mut fields).await?;
+            json!({ "id": schema_id.view_id(), "schema_id": schema_id })
+        }
+        SchemaCommand::Apply { path } => serde_json::to_value(op.apply_schema_file(path).await?)?,
+        SchemaCommand::List => serde_json::to_value(op.get_all_schema_definition().await?)?,
+        SchemaCommand::Show { schema_id } => {
+            let fields = op.get_schema_fields(schema_id).await?;
+            json!({ "schema_id": schema_id, "fields": fields })
+        }
+    };
+
+    Ok(output)
+}
+
+async fn doc(op: &Operator, command: &DocCommand) -> CliResult<Value> {
+    let output = match command {
+        DocCommand::Create { schema_id, fields } => {
+            let definitions = fields.definitions(op, schema_id).await?;
+            let document = op
+                .create_instance(schema_id, &mut fields.document_fields(&definitions)?)
+                .await?;
+            serde_json::to_value(document)?
+        }
+        DocCommand::Update {
+            schema_id,
+            document_id,
+            view_id,
+            fields,
+        } => {
+            let document = DocumentHandle::parse(schema_id, document_id, view_id)?;
+            let definitions = fields.definitions(op, schema_id).await?;
+            let document = op
+                .update_instance(&document, &mut fields.document_fields(&definitions)?)
+                .await?;
+            serde_json::to_value(document)?
+        }
+        DocCommand::Delete {
+            schema_id,
+            document_id,
+            view_id,
+        } => {
+            let document = DocumentHandle::parse(schema_id, document_id, view_id)?;
+            serde_json::to_value(op.delete_instance(&document).await?)?
+        }
+        DocCommand::Get {
+            schema_id,
+            document_id,
+        } => {
+            let document: Document = op.get_document(schema_id, document_id).await?;
+            serde_json::to_value(document)?
+        }
+        DocCommand::List { schema_id } => {
+            let documents: Vec<Document> = op.get_all_documents(schema_id).await?;
+            serde_json::to_value(documents)?
+        }
+    };
+
+    Ok(output)
+}
+
+/// Builds the Operator like `Operator::from_env`, with the options given on the command line
+fn operator(cli: &Cli) -> CliResult<Operator> {
+    let mut builder = Operator::builder().key_store(key_store(cli));
+
+    if let Some(endpoint) = cli.endpoint.clone().or_else(|| env::var("ENDPOINT").ok()) {
+        builder = builder.endpoint(&endpoint);
+    }
+
+    Ok(builder.build()?)
+}
+
+fn key_store(cli: &Cli) -> KeyStore {
+    let key_store = match &cli.key {
+        Some(path) => KeyStore::load(path),
+        None if env::var_os("PRIVATE_KEY").is_some() => KeyStore::from_env("PRIVATE_KEY"),
+        None => KeyStore::load("key.txt"),
+    };
+
+    with_password(key_store, cli)
+}
+
+fn with_password(key_store: KeyStore, cli: &Cli) -> KeyStore {
+    match cli
+        .password
+        .clone()
+        .or_else(|| env::var("KEY_PASSWORD").ok())
+    {
+        Some(password) => key_store.password(&password),
+        None => key_store,
+    }
+}
+
+impl FieldArgs {
+    /// `name=type` pairs and the string values of the JSON object
+    fn schema_fields(&self) -> CliResult<Vec<(String, String)>> {
+        let mut fields = split_pairs(&self.fields)?;
+
+        for (name, value) in self.json_fields()? {
+            match value {
+                Value::String(field_type) => fields.push((name, field_type)),
+                _ => return Err(format!("type of field '{}' must be a string", name).into()),
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// Field definitions of the schema, only fetched when there are `name=value` pairs to parse
+    async fn definitions(&self, op: &Operator, schema_id: &str) -> CliResult<Vec<FieldDefinition>> {
+        if self.fields.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(op.get_schema_fields(schema_id).await?)
+    }
+
+    /// `name=value` pairs, parsed by the type of the field in `definitions`
+    /// or inferred like `zenode::field` for unknown fields, and the values of the JSON object
+    fn document_fields(&self, definitions: &[FieldDefinition]) -> CliResult<Vec<FieldTuple>> {
+        let mut fields: Vec<FieldTuple> = split_pairs(&self.fields)?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match definitions.iter().find(|def| def.name == name) {
+                    Some(def) => typed_value(&name, &def.type_, &value)?,
+                    None => FieldValue::guess(&value),
+                };
+                Ok((name, value))
+            })
+            .collect::<CliResult<_>>()?;
+
+        for (name, value) in self.json_fields()? {
+            let value = json_value(&value)
+                .ok_or_else(|| format!("unsupported value for field '{}': {}", name, value))?;
+            fields.push((name, value));
+        }
+
+        Ok(fields)
+    }
+
+    fn json_fields(&self) -> CliResult<serde_json::Map<String, Value>> {
+        match &self.json {
+            Some(json) => match serde_json::from_str(json)? {
+                Value::Object(fields) => Ok(fields),
+                _ => Err("--json must be an object".into()),
+            },
+            None => Ok(serde_json::Map::new()),
+        }
+    }
+}
+
+fn split_pairs(pairs: &[String]) -> CliResult<Vec<(String, String)>> {
+    pairs
+        .iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => Ok((name.to_string(), value.to_string())),
+            None => Err(format!("expected name=value, got '{}'", pair).into()),
+        })
+        .collect()
+}
+
+/// Parses a command-line value as the given field type
+///
+/// Lists are JSON arrays, pinned relations view ids (`<operation_id>_<operation_id>`) or JSON arrays
+fn typed_value(name: &str, field_type: &str, value: &str) -> CliResult<FieldValue> {
+    let invalid = || format!("field '{}' expects {}, got '{}'", name, field_type, value);
+
+    let value = match FieldType::parse(field_type) {
+        Some(FieldType::Str) => FieldValue::Str(value.to_string()),
+        Some(FieldType::Int) => FieldValue::Int(value.parse().map_err(|_| invalid())?),
+        Some(FieldType::Float) => FieldValue::Float(value.parse().map_err(|_| invalid())?),
+        Some(FieldType::Bool) => FieldValue::Bool(value.parse().map_err(|_| invalid())?),
+        Some(FieldType::Relation(_)) => FieldValue::Relation(value.to_string()),
+        Some(FieldType::RelationList(_)) => {
+            FieldValue::RelationList(serde_json::from_str(value).map_err(|_| invalid())?)
+        }
+        Some(FieldType::PinnedRelation(_)) => FieldValue::PinnedRelation(
+            serde_json::from_str(value)
+                .unwrap_or_else(|_| value.split('_').map(str::to_string).collect()),
+        ),
+        Some(FieldType::PinnedRelationList(_)) => {
+            FieldValue::PinnedRelationList(serde_json::from_str(value).map_err(|_| invalid())?)
+        }
+        // Types this version doesn't know are left to the node
+        None => FieldValue::guess(value),
+    };
+
+    Ok(value)
+}
+
+/// Arrays of ids become relation lists, arrays of arrays pinned relation lists
+fn json_value(value: &Value) -> Option<FieldValue> {
+    match value {
+        Value::Bool(value) => Some(FieldValue::Bool(*value)),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => Some(FieldValue::Int(int)),
+            None => number.as_f64().map(FieldValue::Float),
+        },
+        Value::String(value) => Some(FieldValue::Str(value.clone())),
+        Value::Array(_) => serde_json::from_value(value.clone())
+            .map(FieldValue::RelationList)
+            .or_else(|_| serde_json::from_value(value.clone()).map(FieldValue::PinnedRelationList))
+            .ok(),
+        Value::Null | Value::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_args() {
+        let args = FieldArgs {
+            fields: vec!["name=Tuna".to_string(), "age=3".to_string()],
+            json: Some(r#"{"weight":4.5,"friends":["a","b"],"isFree":true}"#.to_string()),
+        };
+
+        let fields = args.document_fields(&[]).unwrap();
+        assert_eq!(
+            fields[0],
+            ("name".to_string(), FieldValue::Str("Tuna".to_string()))
+        );
+        assert_eq!(fields[1], ("age".to_string(), FieldValue::Int(3)));
+        assert!(fields.contains(&("weight".to_string(), FieldValue::Float(4.5))));
+        assert!(fields.contains(&("isFree".to_string(), FieldValue::Bool(true))));
+        assert!(fields.contains(&(
+            "friends".to_string(),
+            FieldValue::RelationList(vec!["a".to_string(), "b".to_string()])
+        )));
+
+        let args = FieldArgs {
+            fields: vec!["name".to_string()],
+            json: None,
+        };
+        assert!(args.document_fields(&[]).is_err());
+
+        // values are parsed by the type of the field, unknown fields are inferred
+        let definitions = [
+            ("code", "str"),
+            ("title", "str"),
+            ("count", "int"),
+            ("ok", "bool"),
+        ]
+        .map(|(name, type_)| FieldDefinition {
+            name: name.to_string(),
+            type_: type_.to_string(),
+        });
+        let args = FieldArgs {
+            fields: vec![
+                "code=007".to_string(),
+                "title=true".to_string(),
+                "count=7".to_string(),
+                "other=7".to_string(),
+            ],
+            json: None,
+        };
+        let fields = args.document_fields(&definitions).unwrap();
+        assert_eq!(fields[0].1, FieldValue::Str("007".to_string()));
+        assert_eq!(fields[1].1, FieldValue::Str("true".to_string()));
+        assert_eq!(fields[2].1, FieldValue::Int(7));
+        assert_eq!(fields[3].1, FieldValue::Int(7));
+
+        let args = FieldArgs {
+            fields: vec!["ok=yes".to_string()],
+            json: None,
+        };
+        assert!(args.document_fields(&definitions).is_err());
+
+        let args = FieldArgs {
+            fields: vec!["name=str".to_string()],
+            json: Some(r#"{"age":"int"}"#.to_string()),
+        };
+        assert_eq!(
+            args.schema_fields().unwrap(),
+            [
+                ("name".to_string(), "str".to_string()),
+                ("age".to_string(), "int".to_string())
+            ]
+        );
+    }
+}
@@ -11,6 +11,58 @@ pub enum FieldType<'a> {
     PinnedRelationList(&'a str),
 }
 
+impl<'a> FieldType<'a> {
+    /// Parses a field type written like its `Display`, e.g. `"int"` or `"relation(<schema>)"`
+    pub fn parse(field_type: &'a str) -> Option<FieldType<'a>> {
+        use FieldType::*;
+        let parsed = match field_type {
+            "bool" => Bool,
+            "int" => Int,
+            "float" => Float,
+            "str" => Str,
+            _ => {
+                let (kind, target) = field_type.strip_suffix(')')?.split_once('(')?;
+                match kind {
+                    "relation" => Relation(target),
+                    "relation_list" => RelationList(target),
+                    "pinned_relation" => PinnedRelation(target),
+                    "pinned_relation_list" => PinnedRelationList(target),
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(parsed)
+    }
+
+    /// Schema the field relates to, `None` for the primitive types
+    pub fn target(&self) -> Option<&'a str> {
+        use FieldType::*;
+        match self {
+            Bool | Int | Float | Str => None,
+            Relation(target)
+            | RelationList(target)
+            | PinnedRelation(target)
+            | PinnedRelationList(target) => Some(target),
+        }
+    }
+
+    /// The same type relating to `target` instead
+    pub fn with_target<'b>(&self, target: &'b str) -> FieldType<'b> {
+        use FieldType::*;
+        match self {
+            Bool => Bool,
+            Int => Int,
+            Float => Float,
+            Str => Str,
+            Relation(_) => Relation(target),
+            RelationList(_) => RelationList(target),
+            PinnedRelation(_) => PinnedRelation(target),
+            PinnedRelationList(_) => PinnedRelationList(target),
+        }
+    }
+}
+
 impl Display for FieldType<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use FieldType::*;
@@ -26,3 +78,102 @@ impl Display for FieldType<'_> {
         }
     }
 }
+
+/// Value of a document field
+///
+/// Values are encoded exactly as given, use `FieldValue::guess` to infer the type from a string
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// Document id of the related document
+    Relation(String),
+    /// Document ids of the related documents
+    RelationList(Vec<String>),
+    /// Document view id (operation ids) of the related document
+    PinnedRelation(Vec<String>),
+    /// Document view ids of the related documents
+    PinnedRelationList(Vec<Vec<String>>),
+}
+
+impl FieldValue {
+    /// Infers the value from a string, this is how `field(name, value)` works
+    ///
+    /// `"true"/"false"` become `Bool`, integers `Int`, decimals `Float`,
+    /// `"[...]"` json arrays `RelationList` or `PinnedRelationList` and anything else `Str`
+    pub fn guess(value: &str) -> FieldValue {
+        if let Ok(b) = value.parse::<bool>() {
+            return FieldValue::Bool(b);
+        }
+
+        // For relation_list, pinned_relation and pinned_relation_list
+        if value.starts_with('[') && value.ends_with(']') {
+            if let Ok(ids) = serde_json::from_str::<Vec<String>>(value) {
+                return FieldValue::RelationList(ids);
+            }
+
+            if let Ok(ids) = serde_json::from_str::<Vec<Vec<String>>>(value) {
+                return FieldValue::PinnedRelationList(ids);
+            }
+        }
+
+        if let Ok(x) = value.parse::<i64>() {
+            return FieldValue::Int(x);
+        }
+
+        match value.parse::<f64>() {
+            Ok(x) if x.is_finite() => FieldValue::Float(x),
+            _ => FieldValue::Str(value.to_string()),
+        }
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        FieldValue::Int(value.into())
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        FieldValue::Int(value.into())
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Int(value)
+    }
+}
+
+impl From<f32> for FieldValue {
+    fn from(value: f32) -> Self {
+        FieldValue::Float(value.into())
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Str(value.to_string())
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::Str(value)
+    }
+}
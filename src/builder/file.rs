@@ -0,0 +1,243 @@
+//! Declarative schema files, applied with `Operator::apply_schema_file`
+//!
+//! ```toml
+//! [[schemas]]
+//! name = "owner"
+//! description = "Pet owner"
+//! fields = { name = "str" }
+//!
+//! [[schemas]]
+//! name = "pet"
+//! description = "Pet with an owner"
+//! fields = { name = "str", owner = "relation(owner)" }
+//! ```
+//! The same structure can be written in YAML (`.yaml`, `.yml`) or JSON (`.json`).
+//! Relations reference schemas by name, either schemas of the same file or schemas
+//! of the `SchemaRegistry`. Full schema ids are accepted as well
+use crate::builder::fields::FieldType;
+use crate::builder::graph::dependency_order;
+use crate::ids::SchemaId;
+use crate::operator::{Operator, StringTuple};
+use crate::{Error, Result};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Content of a schema file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SchemaFile {
+    pub schemas: Vec<SchemaSpec>,
+}
+
+/// Schema declared in a schema file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SchemaSpec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Field names and types, relations name their target schema, e.g. `relation(owner)`
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Schema recorded in the lockfile of a schema file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedSchema {
+    pub schema_id: SchemaId,
+    pub description: String,
+    /// Field names and types with resolved schema ids, sorted by name
+    pub fields: Vec<StringTuple>,
+}
+
+impl SchemaFile {
+    /// Reads a schema file, the format is chosen by the extension: `toml`, `yaml`/`yml` or `json`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|err| schema_file_error(path, err))?;
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        match extension {
+            "toml" => toml::from_str(&content).map_err(|err| schema_file_error(path, err)),
+            "yaml" | "yml" => {
+                serde_yaml::from_str(&content).map_err(|err| schema_file_error(path, err))
+            }
+            "json" => serde_json::from_str(&content).map_err(|err| schema_file_error(path, err)),
+            _ => Err(schema_file_error(
+                path,
+                "unknown format, expected a .toml, .yaml, .yml or .json file",
+            )),
+        }
+    }
+
+    /// Orders the schemas so that every schema comes after the schemas of the file it relates to
+    ///
+    /// Fails for duplicate names, invalid field types and relation cycles
+    pub fn sorted(&self) -> std::result::Result<Vec<&SchemaSpec>, String> {
+        let names: Vec<&str> = self.schemas.iter().map(|s| s.name.as_str()).collect();
+        let dependencies = self
+            .schemas
+            .iter()
+            .map(SchemaSpec::targets)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let order = dependency_order(&names, &dependencies)?;
+        Ok(order.into_iter().map(|i| &self.schemas[i]).collect())
+    }
+}
+
+impl SchemaSpec {
+    /// Names or ids of the schemas the fields relate to
+    fn targets(&self) -> std::result::Result<Vec<&str>, String> {
+        self.fields
+            .iter()
+            .map(|(name, field_type)| Ok(parse_type(&self.name, name, field_type)?.target()))
+            .filter_map(|target| target.transpose())
+            .collect()
+    }
+}
+
+impl Operator {
+    /// Creates the schemas of a schema file in dependency order, reusing existing ones
+    ///
+    /// Relation targets are resolved to the schema ids applied before them, the `SchemaRegistry`
+    /// or taken as schema ids. Every schema is created with `ensure_schema`, unless the lockfile
+    /// `<path>.lock` already records it with the same description and fields.
+    /// The lockfile is updated after every applied schema, returns the schema ids by name
+    pub async fn apply_schema_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<BTreeMap<String, SchemaId>> {
+        let path = path.as_ref();
+        let file = SchemaFile::open(path)?;
+        let sorted = file.sorted().map_err(|err| schema_file_error(path, err))?;
+
+        let lock_path = lock_path(path);
+        let mut lock = read_lock(&lock_path)?;
+        let mut schema_ids: BTreeMap<String, SchemaId> = BTreeMap::new();
+
+        for schema in sorted {
+            let mut fields: Vec<StringTuple> = Vec::new();
+            for (name, field_type) in &schema.fields {
+                let field_type = parse_type(&schema.name, name, field_type)
+                    .map_err(|err| schema_file_error(path, err))?;
+
+                let target = match field_type.target() {
+                    Some(target) => Some(
+                        self.resolve_target(target, &schema_ids)
+                            .map_err(|err| schema_file_error(path, err))?,
+                    ),
+                    None => None,
+                };
+
+                let field_type = match &target {
+                    Some(target) => field_type.with_target(target).to_string(),
+                    None => field_type.to_string(),
+                };
+                fields.push((name.clone(), field_type));
+            }
+
+            let locked = lock.get(&schema.name).filter(|locked| {
+                locked.description == schema.description && locked.fields == fields
+            });
+
+            let changed = locked.is_none();
+            let schema_id = match locked {
+                Some(locked) => {
+                    let schema_id = locked.schema_id.clone();
+                    self.record_schema(&schema.name, &schema_id, &schema.description, &fields)
+                        .await?;
+                    schema_id
+                }
+                None => {
+                    self.ensure_schema(&schema.name, &schema.description, &mut fields)
+                        .await?
+                }
+            };
+
+            lock.insert(
+                schema.name.clone(),
+                LockedSchema {
+                    schema_id: schema_id.clone(),
+                    description: schema.description.clone(),
+                    fields,
+                },
+            );
+            schema_ids.insert(schema.name.clone(), schema_id);
+
+            // Record every applied schema, so a later failure doesn't create it again
+            if changed {
+                write_lock(&lock_path, &lock)?;
+            }
+        }
+
+        write_lock(&lock_path, &lock)?;
+        Ok(schema_ids)
+    }
+
+    /// Resolves a relation target to a schema applied before, a registered schema or a schema id
+    fn resolve_target(
+        &self,
+        target: &str,
+        applied: &BTreeMap<String, SchemaId>,
+    ) -> std::result::Result<String, String> {
+        if let Some(schema_id) = applied.get(target) {
+            return Ok(schema_id.to_string());
+        }
+
+        if let Some(schema_id) = self.registry().schema_id(target) {
+            return Ok(schema_id.to_string());
+        }
+
+        target
+            .parse::<SchemaId>()
+            .map(|schema_id| schema_id.to_string())
+            .map_err(|_| format!("unknown schema '{}'", target))
+    }
+}
+
+/// Lockfile of a schema file, `schemas.toml` -> `schemas.toml.lock`
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+}
+
+fn read_lock(path: &Path) -> Result<BTreeMap<String, LockedSchema>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(path).map_err(|err| schema_file_error(path, err))?;
+    serde_json::from_str(&content).map_err(|err| schema_file_error(path, err))
+}
+
+fn write_lock(path: &Path, lock: &BTreeMap<String, LockedSchema>) -> Result<()> {
+    let content = serde_json::to_string_pretty(lock)?;
+
+    // Write to a temporary file first so a crash can't leave a half written lockfile
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content)
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|err| schema_file_error(path, err))
+}
+
+fn parse_type<'a>(
+    schema: &str,
+    field: &str,
+    field_type: &'a str,
+) -> std::result::Result<FieldType<'a>, String> {
+    FieldType::parse(field_type).ok_or_else(|| {
+        format!(
+            "field '{}' of schema '{}' has the invalid type '{}'",
+            field, schema, field_type
+        )
+    })
+}
+
+fn schema_file_error(path: &Path, reason: impl ToString) -> Error {
+    Error::SchemaFile {
+        path: path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
@@ -0,0 +1,160 @@
+use crate::builder::SchemaBuilder;
+use crate::{Error, Result};
+
+use std::collections::HashMap;
+
+/// Set of `SchemaBuilder`s relating to each other with `SchemaBuilder::relation_to`
+///
+/// ```ignore
+/// let owner = SchemaBuilder::new("owner", "Pet owner", &op).field("name", Str);
+/// let pet = SchemaBuilder::new("pet", "Pet", &op).relation_to("owner", &owner);
+///
+/// let mut graph = SchemaGraph::new().schema(pet).schema(owner);
+/// graph.build().await?; // builds owner, then pet
+/// let pet_id = graph.schema_id("pet");
+/// ```
+#[derive(Default)]
+pub struct SchemaGraph<'a> {
+    builders: Vec<SchemaBuilder<'a>>,
+}
+
+impl<'a> SchemaGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            builders: Vec::new(),
+        }
+    }
+
+    pub fn schema(mut self, builder: SchemaBuilder<'a>) -> Self {
+        self.builders.push(builder);
+        self
+    }
+
+    /// Builds every schema after the schemas it relates to
+    ///
+    /// Relations to builders which are neither built nor part of the graph, duplicate names,
+    /// cycles, invalid names and fields are rejected before anything is published
+    pub async fn build(&mut self) -> Result<()> {
+        let order = self.order()?;
+        let mut built: HashMap<String, String> = HashMap::new();
+
+        for index in order {
+            let builder = &mut self.builders[index];
+
+            for field in &mut builder.fields {
+                if let Some(related) = &mut field.related {
+                    if related.schema_id.is_none() {
+                        related.schema_id = built.get(&related.name).cloned();
+                    }
+                }
+            }
+
+            builder.build().await?;
+            built.insert(builder.name.clone(), builder.schema_id.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the builder with the respective name
+    pub fn get(&self, name: &str) -> Option<&SchemaBuilder<'a>> {
+        self.builders.iter().find(|builder| builder.name == name)
+    }
+
+    /// Returns the schema id of the builder with the respective name once it's built
+    pub fn schema_id(&self, name: &str) -> Option<&str> {
+        self.get(name)
+            .map(|builder| builder.schema_id.as_str())
+            .filter(|schema_id| !schema_id.is_empty())
+    }
+
+    pub fn into_builders(self) -> Vec<SchemaBuilder<'a>> {
+        self.builders
+    }
+
+    /// Indices of the builders in the order they have to be built, after checking every builder
+    fn order(&self) -> Result<Vec<usize>> {
+        let names: Vec<&str> = self.builders.iter().map(|b| b.name.as_str()).collect();
+        let mut dependencies = Vec::new();
+
+        for builder in &self.builders {
+            let mut targets = Vec::new();
+            for field in &builder.fields {
+                let related = match &field.related {
+                    Some(related) if related.schema_id.is_none() => related,
+                    _ => continue,
+                };
+
+                if !names.contains(&related.name.as_str()) {
+                    return Err(Error::SchemaGraph(format!(
+                        "field '{}' of '{}' relates to '{}', which is neither built nor part of the graph",
+                        field.name, builder.name, related.name
+                    )));
+                }
+                targets.push(related.name.as_str());
+            }
+            dependencies.push(targets);
+        }
+
+        let order = dependency_order(&names, &dependencies).map_err(Error::SchemaGraph)?;
+
+        for builder in &self.builders {
+            builder.check()?;
+        }
+
+        Ok(order)
+    }
+}
+
+/// Orders `names` so that every name comes after its `dependencies` (names missing in `names` are ignored)
+///
+/// Returns the indices into `names`, fails for duplicate names and cycles
+pub(crate) fn dependency_order(
+    names: &[&str],
+    dependencies: &[Vec<&str>],
+) -> std::result::Result<Vec<usize>, String> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        if index.insert(name, i).is_some() {
+            return Err(format!("schema '{}' is declared twice", name));
+        }
+    }
+
+    // Depth first search, `visiting` holds the current path to report cycles
+    fn visit(
+        i: usize,
+        names: &[&str],
+        dependencies: &[Vec<&str>],
+        index: &HashMap<&str, usize>,
+        visiting: &mut Vec<usize>,
+        sorted: &mut Vec<usize>,
+    ) -> std::result::Result<(), String> {
+        if sorted.contains(&i) {
+            return Ok(());
+        }
+
+        if let Some(start) = visiting.iter().position(|&v| v == i) {
+            let mut cycle: Vec<&str> = visiting[start..].iter().map(|&v| names[v]).collect();
+            cycle.push(names[i]);
+            return Err(format!("relation cycle {}", cycle.join(" -> ")));
+        }
+
+        visiting.push(i);
+        for dependency in &dependencies[i] {
+            if let Some(&d) = index.get(dependency) {
+                visit(d, names, dependencies, index, visiting, sorted)?;
+            }
+        }
+        visiting.pop();
+
+        sorted.push(i);
+        Ok(())
+    }
+
+    let mut sorted = Vec::new();
+    for i in 0..names.len() {
+        visit(i, names, dependencies, &index, &mut Vec::new(), &mut sorted)?;
+    }
+
+    Ok(sorted)
+}
@@ -0,0 +1,62 @@
+use crate::graphql::schemas::SchemaDefinition;
+use crate::operator::StringTuple;
+
+/// Change applied to an existing schema by `Operator::update_schema`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchemaChange {
+    /// Adds a field with the respective name and type (`FieldType::to_string()`)
+    AddField(String, String),
+    /// Removes the field with the respective name
+    RemoveField(String),
+    /// Replaces the description of the schema
+    Description(String),
+}
+
+impl SchemaChange {
+    /// Utility function to add a field, same signature as `field_def`
+    pub fn add(name: &str, field_type: crate::FieldType) -> Self {
+        SchemaChange::AddField(name.to_string(), field_type.to_string())
+    }
+
+    /// Utility function to remove a field
+    pub fn remove(name: &str) -> Self {
+        SchemaChange::RemoveField(name.to_string())
+    }
+}
+
+/// Computes the changes to turn the `current` schema definition into one with
+/// the `description` and `fields`
+///
+/// A field whose type changed is removed and added again
+pub fn diff_schema(
+    current: &SchemaDefinition,
+    description: &str,
+    fields: &[StringTuple],
+) -> Vec<SchemaChange> {
+    let existing: Vec<(&str, &str)> = current
+        .fields
+        .fields
+        .iter()
+        .map(|f| (f.fields.name.as_str(), f.fields.type_.as_str()))
+        .collect();
+
+    let mut changes = Vec::new();
+
+    if current.fields.description != description {
+        changes.push(SchemaChange::Description(description.to_string()));
+    }
+
+    for (name, field_type) in existing.iter() {
+        if !fields.iter().any(|(n, t)| n == name && t == field_type) {
+            changes.push(SchemaChange::RemoveField(name.to_string()));
+        }
+    }
+
+    for (name, field_type) in fields {
+        if !existing.iter().any(|(n, t)| n == name && t == field_type) {
+            changes.push(SchemaChange::AddField(name.clone(), field_type.clone()));
+        }
+    }
+
+    changes
+}
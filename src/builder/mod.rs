@@ -1,6 +1,12 @@
 pub mod fields;
+pub mod file;
+pub mod graph;
+pub mod migration;
 use crate::builder::fields::*;
+use crate::builder::migration::diff_schema;
+use crate::ids::{DocumentHandle, SchemaId};
 use crate::operator::*;
+use crate::{Error, Result};
 use std::convert::AsRef;
 
 // ---- Builders ----
@@ -32,25 +38,140 @@ impl<'a> SchemaBuilder<'a> {
         self
     }
 
-    pub async fn build(&mut self) -> Result<(), String> {
+    /// Adds a relation to the schema of `other`, which may be built later by a `SchemaGraph`
+    pub fn relation_to(self, field_name: &str, other: &SchemaBuilder) -> Self {
+        self.related_field(field_name, FieldType::Relation(""), other)
+    }
+
+    /// Adds a relation list to the schema of `other`, see `relation_to`
+    pub fn relation_list_to(self, field_name: &str, other: &SchemaBuilder) -> Self {
+        self.related_field(field_name, FieldType::RelationList(""), other)
+    }
+
+    /// Adds a pinned relation to the schema of `other`, see `relation_to`
+    pub fn pinned_relation_to(self, field_name: &str, other: &SchemaBuilder) -> Self {
+        self.related_field(field_name, FieldType::PinnedRelation(""), other)
+    }
+
+    /// Adds a pinned relation list to the schema of `other`, see `relation_to`
+    pub fn pinned_relation_list_to(self, field_name: &str, other: &SchemaBuilder) -> Self {
+        self.related_field(field_name, FieldType::PinnedRelationList(""), other)
+    }
+
+    fn related_field(
+        mut self,
+        field_name: &str,
+        field_type: FieldType<'a>,
+        other: &SchemaBuilder,
+    ) -> Self {
+        let schema_id = Some(other.schema_id.clone()).filter(|id| !id.is_empty());
+        self.fields.push(SchemaField {
+            name: field_name.to_string(),
+            field_type,
+            related: Some(RelatedSchema {
+                name: other.name.clone(),
+                schema_id,
+            }),
+        });
+        self
+    }
+
+    /// Field names and types, fails for relations whose target isn't known yet
+    fn field_defs(&self) -> Result<Vec<StringTuple>> {
+        self.fields.iter().map(|f| self.field_def(f)).collect()
+    }
+
+    fn field_def(&self, f: &SchemaField) -> Result<StringTuple> {
+        let field_type = match &f.related {
+            Some(RelatedSchema {
+                schema_id: Some(schema_id),
+                ..
+            }) => f.field_type.with_target(schema_id).to_string(),
+            Some(related) => {
+                return Err(Error::SchemaGraph(format!(
+                    "field '{}' of '{}' relates to '{}', which is not built yet",
+                    f.name, self.name, related.name
+                )))
+            }
+            None if f.field_type.target() == Some("") => {
+                return Err(Error::SchemaGraph(format!(
+                    "field '{}' of '{}' relates to an empty schema id",
+                    f.name, self.name
+                )))
+            }
+            None => f.field_type.to_string(),
+        };
+
+        Ok((f.name.clone(), field_type))
+    }
+
+    /// Checks the name and fields like `build` does before publishing,
+    /// relations to builders which aren't built yet are left to the `SchemaGraph`
+    pub(crate) fn check(&self) -> Result<()> {
+        check_schema_name(&self.name)?;
+
+        for f in &self.fields {
+            match &f.related {
+                Some(RelatedSchema {
+                    schema_id: None, ..
+                }) => continue,
+                _ => operations::schema_field_definition(&self.field_def(f)?)?,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Creates the schema, fails before publishing anything if a relation target is not built
+    pub async fn build(&mut self) -> Result<()> {
         // struct schema field -> (name, type)
-        let mut fields: Vec<StringTuple> = self
-            .fields
-            .iter()
-            .map(|f| -> StringTuple { (f.name.clone(), f.field_type.to_string()) })
-            .collect();
+        let mut fields = self.field_defs()?;
 
-        let operation_id = self
+        let schema_id = self
             .operator
             .create_schema(&self.name, &self.description, &mut fields)
             .await?;
 
-        self.schema_id = format!("{}_{}", self.name, &operation_id);
-        self.operation_id = operation_id;
+        self.set_schema_id(schema_id);
+        Ok(())
+    }
+
+    /// Migrates the schema with the respective `schema_id` to the fields and description of the builder
+    ///
+    /// Only the differences are published, if there are none the schema id stays the same
+    pub async fn migrate(&mut self, schema_id: &str) -> Result<()> {
+        let schema_id = self.operator.resolve_schema_id(schema_id);
+        // A bare view id of the schema definition is accepted as well
+        let view_id = match schema_id.parse::<SchemaId>().map(|id| id.view_id()) {
+            Ok(Some(view_id)) => view_id.to_string(),
+            _ => schema_id.clone(),
+        };
+
+        let fields = self.field_defs()?;
+        let current = self.operator.find_schema_definition(&view_id).await?;
+
+        let changes = diff_schema(&current, &self.description, &fields);
+
+        if changes.is_empty() {
+            self.schema_id = schema_id;
+            self.operation_id = view_id;
+            return Ok(());
+        }
+
+        let schema_id = self.operator.update_schema(&view_id, &changes).await?;
+        self.set_schema_id(schema_id);
         Ok(())
     }
 
-    pub async fn instantiate(&self, fields: &mut [StringTuple]) -> Result<String, String> {
+    fn set_schema_id(&mut self, schema_id: SchemaId) {
+        self.operation_id = schema_id
+            .view_id()
+            .map(|view_id| view_id.to_string())
+            .unwrap_or_default();
+        self.schema_id = schema_id.to_string();
+    }
+
+    pub async fn instantiate(&self, fields: &mut [FieldTuple]) -> Result<DocumentHandle> {
         self.operator.create_instance(&self.schema_id, fields).await
     }
 }
@@ -64,10 +185,25 @@ impl<'a> AsRef<SchemaBuilder<'a>> for SchemaBuilder<'a> {
 pub struct SchemaField<'a> {
     pub name: String,
     pub field_type: FieldType<'a>,
+    /// Builder the relation was created from with `SchemaBuilder::relation_to`,
+    /// its schema id replaces the target of `field_type`
+    pub related: Option<RelatedSchema>,
 }
 
 impl SchemaField<'_> {
-    pub fn new(name: String, field_type: FieldType<'_>) -> SchemaField {
-        SchemaField { name, field_type }
+    pub fn new(name: String, field_type: FieldType<'_>) -> SchemaField<'_> {
+        SchemaField {
+            name,
+            field_type,
+            related: None,
+        }
     }
 }
+
+/// Target of a relation to another `SchemaBuilder`
+pub struct RelatedSchema {
+    /// Name of the related builder
+    pub name: String,
+    /// Schema id of the related builder, `None` until it's built
+    pub schema_id: Option<String>,
+}
@@ -0,0 +1,114 @@
+use crate::graphql::queries::{next_args_query, publish_mutation};
+use crate::graphql::schemas::{NextArgsResponse, NextArguments, PublishResponse};
+use crate::{Error, Result};
+
+use async_trait::async_trait;
+use gql_client::Client;
+use p2panda_rs::document::DocumentViewId;
+use p2panda_rs::entry::EncodedEntry;
+use p2panda_rs::identity::PublicKey;
+use p2panda_rs::operation::EncodedOperation;
+use serde_json::Value;
+
+/// Transport used by the `Operator` to talk to a p2panda node
+///
+/// `GraphQLClient` is the default, other implementations are set with `OperatorBuilder::client`
+#[async_trait]
+pub trait NodeClient: Send + Sync {
+    /// Returns the arguments for the next entry of `public_key`
+    ///
+    /// `view_id` is the document view the operation is based on, `None` for CREATE operations
+    async fn next_args(
+        &self,
+        public_key: &PublicKey,
+        view_id: Option<&DocumentViewId>,
+    ) -> Result<NextArguments>;
+
+    /// Publishes a signed entry and its operation,
+    /// returns the arguments for the next entry in the same log
+    ///
+    /// Rejections because the log changed meanwhile, e.g. the seq num is taken, are returned as
+    /// `Error::LogConflict`, so the `Operator` signs the entry again with new arguments
+    async fn publish(
+        &self,
+        entry: &EncodedEntry,
+        operation: &EncodedOperation,
+    ) -> Result<NextArguments>;
+
+    /// Runs a GraphQL query and returns the `data` of the response
+    async fn query(&self, query: &str, variables: Option<Value>) -> Result<Value>;
+}
+
+/// Talks to the GraphQL endpoint of a node, e.g. `http://localhost:2020/graphql`
+pub struct GraphQLClient {
+    client: Client,
+}
+
+impl GraphQLClient {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            client: Client::new(endpoint),
+        }
+    }
+}
+
+#[async_trait]
+impl NodeClient for GraphQLClient {
+    async fn next_args(
+        &self,
+        public_key: &PublicKey,
+        view_id: Option<&DocumentViewId>,
+    ) -> Result<NextArguments> {
+        let view_id = view_id.map(|id| id.to_string());
+        let query = next_args_query(&public_key.to_string(), view_id.as_deref());
+
+        let response: NextArgsResponse = self.client.query_unwrap(&query).await?;
+        Ok(response.next_args)
+    }
+
+    async fn publish(
+        &self,
+        entry: &EncodedEntry,
+        operation: &EncodedOperation,
+    ) -> Result<NextArguments> {
+        let query = publish_mutation(&entry.to_string(), &operation.to_string());
+
+        let response: PublishResponse =
+            self.client
+                .query_unwrap(&query)
+                .await
+                .map_err(|err| match Error::from(err) {
+                    Error::GraphQL(err) => match log_conflict(&err.to_string()) {
+                        Some(message) => Error::LogConflict(message.to_string()),
+                        None => Error::GraphQL(err),
+                    },
+                    err => err,
+                })?;
+        Ok(response.publish)
+    }
+
+    async fn query(&self, query: &str, variables: Option<Value>) -> Result<Value> {
+        let data = match variables {
+            Some(variables) => self.client.query_with_vars_unwrap(query, variables).await?,
+            None => self.client.query_unwrap(query).await?,
+        };
+
+        Ok(data)
+    }
+}
+
+/// Returns the message of a publish error saying the log of the entry changed meanwhile
+///
+/// Relies on the messages aquadoggo returns when validating the seq num and log id of an entry:
+/// - "Entry's claimed seq num of {} does not match expected seq num of {} for given public key and log"
+/// - "Entry's claimed log id of {} does not match expected next log id of {} for given public key"
+/// - "Entry's claimed log id of {} does not match existing log id of {} for given public key and document id"
+fn log_conflict(errors: &str) -> Option<&str> {
+    errors.lines().find_map(|line| {
+        let message = line.strip_prefix("Message: ")?;
+        ["Entry's claimed seq num of", "Entry's claimed log id of"]
+            .iter()
+            .any(|prefix| message.starts_with(prefix))
+            .then_some(message)
+    })
+}
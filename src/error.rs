@@ -0,0 +1,203 @@
+use crate::operator::FieldProblem;
+use gql_client::GraphQLError;
+use p2panda_rs::document::error::{DocumentIdError, DocumentViewIdError};
+use p2panda_rs::entry::error::EncodeEntryError;
+use p2panda_rs::identity::error::KeyPairError;
+use p2panda_rs::operation::error::{EncodeOperationError, OperationBuilderError, OperationIdError};
+use p2panda_rs::schema::error::SchemaIdError;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Convenience alias used by every fallible zenode function
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the `Operator` and the builders
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The request never produced a GraphQL response (connection refused, timeout, bad body...)
+    #[error("could not reach the node: {}", .0.message())]
+    Transport(#[source] GraphQLClientError),
+
+    /// The node answered with a GraphQL `errors` payload
+    #[error("node responded with an error:{0}")]
+    GraphQL(#[source] GraphQLClientError),
+
+    /// The node rejected an entry because its log changed meanwhile, e.g. another entry took its
+    /// seq num, so signing it again with new arguments might help
+    #[error("log conflict: {0}")]
+    LogConflict(String),
+
+    /// A custom `NodeClient` could not reach the node
+    #[error("could not reach the node: {0}")]
+    ClientTransport(String),
+
+    /// The node rejected the request of a custom `NodeClient`
+    #[error("node responded with an error: {0}")]
+    ClientResponse(String),
+
+    /// An operation or a node response could not be converted from/to JSON
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The operation is malformed, e.g. an UPDATE without fields
+    #[error("invalid operation: {0}")]
+    InvalidOperation(#[from] OperationBuilderError),
+
+    /// The operation could not be CBOR encoded
+    #[error("could not encode operation: {0}")]
+    OperationEncoding(#[from] EncodeOperationError),
+
+    /// The entry could not be signed or encoded
+    #[error("could not sign and encode entry: {0}")]
+    EntrySigning(#[from] EncodeEntryError),
+
+    /// The `Signer` failed or returned an invalid signature
+    #[error("signer failed: {0}")]
+    Signer(String),
+
+    /// The fields don't match the definition of the schema, lists every problem
+    #[error("invalid fields for schema '{schema_id}': {}", join_problems(.problems))]
+    InvalidFields {
+        schema_id: String,
+        problems: Vec<FieldProblem>,
+    },
+
+    /// A field value can't be represented as the requested p2panda value
+    #[error("invalid value for field '{name}': {reason}")]
+    InvalidFieldValue { name: String, reason: String },
+
+    /// The node doesn't know the requested schema
+    #[error("schema '{0}' not found")]
+    SchemaNotFound(String),
+
+    /// The requested schema change can't be applied to the current schema
+    #[error("invalid schema change: {0}")]
+    InvalidSchemaChange(String),
+
+    /// The schema id is not a valid p2panda schema id
+    #[error("invalid schema id '{id}': {source}")]
+    InvalidSchemaId { id: String, source: SchemaIdError },
+
+    /// The document view id is not a valid p2panda document view id
+    #[error("invalid document view id '{id}': {source}")]
+    InvalidViewId {
+        id: String,
+        source: DocumentViewIdError,
+    },
+
+    /// The document id is not a valid p2panda document id
+    #[error("invalid document id '{id}': {source}")]
+    InvalidDocumentId { id: String, source: DocumentIdError },
+
+    /// The operation id is not a valid p2panda operation id
+    #[error("invalid operation id '{id}': {source}")]
+    InvalidOperationId {
+        id: String,
+        source: OperationIdError,
+    },
+
+    /// The node doesn't know the requested document
+    #[error("document '{0}' not found")]
+    DocumentNotFound(String),
+
+    /// The operation version is not supported by p2panda-rs
+    #[error("unsupported operation version {0}")]
+    UnsupportedVersion(usize),
+
+    /// The schema registry file could not be read or written
+    #[error("could not access schema registry {path:?}: {source}")]
+    RegistryFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// The outbox file could not be read or written
+    #[error("could not access outbox {path:?}: {source}")]
+    OutboxFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// An entry can't be signed offline or the Operator has no outbox
+    #[error("outbox: {0}")]
+    Outbox(String),
+
+    /// The relations of a `SchemaBuilder` or `SchemaGraph` can't be resolved, e.g. because of a cycle
+    #[error("invalid schema graph: {0}")]
+    SchemaGraph(String),
+
+    /// The schema file or its lockfile could not be read, parsed or written
+    #[error("invalid schema file {path:?}: {reason}")]
+    SchemaFile { path: PathBuf, reason: String },
+
+    /// The key file could not be read or written
+    #[error("could not access key file {path:?}: {source}")]
+    KeyFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// The key file, environment variable or bytes don't contain a valid private key
+    #[error("invalid private key in {origin}: {source}")]
+    InvalidKey {
+        origin: String,
+        source: KeyPairError,
+    },
+
+    /// The environment variable of a `KeyStore` is not set
+    #[error("environment variable {0} with the private key is not set")]
+    MissingKeyEnv(String),
+
+    /// The private key could not be encrypted or decrypted, e.g. because of a wrong password
+    #[error("key encryption failed: {0}")]
+    KeyEncryption(String),
+}
+
+impl From<GraphQLError> for Error {
+    /// Splits gql_client errors into transport failures and GraphQL error payloads
+    fn from(err: GraphQLError) -> Self {
+        if err.json().is_some() {
+            Error::GraphQL(GraphQLClientError(err))
+        } else {
+            Error::Transport(GraphQLClientError(err))
+        }
+    }
+}
+
+/// Error of the gql_client crate, carried as the source of `Error::Transport` and `Error::GraphQL`
+#[derive(Debug, Clone)]
+pub struct GraphQLClientError(pub GraphQLError);
+
+impl GraphQLClientError {
+    pub fn message(&self) -> &str {
+        self.0.message()
+    }
+}
+
+impl std::fmt::Display for GraphQLClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for GraphQLClientError {}
+
+impl Error {
+    /// Returns `true` if the error was caused by the network and retrying might help
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Error::Transport(_) | Error::ClientTransport(_))
+    }
+
+    /// Returns `true` for `Error::LogConflict`
+    pub fn is_log_conflict(&self) -> bool {
+        matches!(self, Error::LogConflict(_))
+    }
+}
+
+fn join_problems(problems: &[FieldProblem]) -> String {
+    problems
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,3 +1,42 @@
+use crate::graphql::schemas::FieldDefinition;
+
+/// Query for the arguments of the next entry of `public_key`
+///
+/// `view_id` is the document view the operation is based on, `None` for CREATE operations
+pub fn next_args_query(public_key: &str, view_id: Option<&str>) -> String {
+    format!(
+        r#"
+            {{
+                nextArgs(publicKey: "{}", viewId: {}) {{
+                    logId
+                    seqNum
+                    skiplink
+                    backlink
+                }}
+            }}
+            "#,
+        public_key,
+        view_id.map_or("null".to_owned(), |id| format!("\"{}\"", id)),
+    )
+}
+
+/// Mutation to publish a hex encoded entry and operation
+pub fn publish_mutation(entry: &str, operation: &str) -> String {
+    format!(
+        r#"
+            mutation Publish {{
+                publish(entry: "{}", operation: "{}") {{
+                    logId
+                    seqNum
+                    skiplink
+                    backlink
+                }}
+            }}
+        "#,
+        entry, operation
+    )
+}
+
 #[allow(non_upper_case_globals)]
 pub const get_all_schemas_query: &str = r#"query {
   allSchemas: all_schema_definition_v1 {
@@ -9,6 +48,10 @@ pub const get_all_schemas_query: &str = r#"query {
           name
           description
           fields {
+            meta {
+              documentId
+              viewId
+            }
             fields {
               name
               type
@@ -30,6 +73,10 @@ pub const get_schema_query: &str = r#"query Schema($id: DocumentId!, $viewId: Do
       name
       description
       fields {
+        meta {
+          documentId
+          viewId
+        }
         fields {
           name
           type
@@ -38,3 +85,55 @@ pub const get_schema_query: &str = r#"query Schema($id: DocumentId!, $viewId: Do
     }
   }
 }"#;
+
+/// Builds the `fields { ... }` selection for the documents of an application schema
+///
+/// Relation fields are resolved by the node to documents,
+/// so only their `meta` is selected: `rel { meta { documentId viewId } }`
+pub fn document_fields_selection(fields: &[FieldDefinition]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            if f.type_.contains("relation") {
+                format!("{} {{ meta {{ documentId viewId }} }}", f.name)
+            } else {
+                f.name.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Query to fetch a single document of the schema with the respective `schema_id`
+pub fn get_document_query(schema_id: &str, fields: &[FieldDefinition]) -> String {
+    format!(
+        r#"query Document($id: DocumentId!) {{
+  document: {}(id: $id) {{
+    meta {{
+      documentId
+      viewId
+    }}
+    fields {{ {} }}
+  }}
+}}"#,
+        schema_id,
+        document_fields_selection(fields)
+    )
+}
+
+/// Query to fetch all the documents of the schema with the respective `schema_id`
+pub fn get_all_documents_query(schema_id: &str, fields: &[FieldDefinition]) -> String {
+    format!(
+        r#"query {{
+  documents: all_{} {{
+    meta {{
+      documentId
+      viewId
+    }}
+    fields {{ {} }}
+  }}
+}}"#,
+        schema_id,
+        document_fields_selection(fields)
+    )
+}
@@ -16,16 +16,14 @@ pub struct NextArgsResponse {
 }
 
 /// GraphQL response for `publish` mutation.
-// #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-#[allow(dead_code)]
 pub struct PublishResponse {
     pub publish: NextArguments,
 }
 
 /// GraphQL response giving us the next arguments to create an Bamboo entry.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct NextArguments {
     pub log_id: LogId,
@@ -49,6 +47,27 @@ pub struct SchemaDefinitionResponse {
     pub schema: SchemaDefinition,
 }
 
+/// Same as `SchemaDefinitionResponse` but the node returns `null` for unknown schemas
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MaybeSchemaDefinitionResponse {
+    pub schema: Option<SchemaDefinition>,
+}
+
+/// GraphQL response for the `<schema_id>(id: ...)` query
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocumentResponse {
+    pub document: Option<Document>,
+}
+
+/// GraphQL response for the `all_<schema_id>` query
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AllDocumentsResponse {
+    pub documents: Vec<Document>,
+}
+
 // GraphQL Schemas
 // ------------------------------------------------
 
@@ -59,6 +78,20 @@ pub struct SchemaDefinition {
     pub fields: SchemaDefinitionFields,
 }
 
+/// Field values of a document, keyed by field name
+pub type FieldMap = serde_json::Map<String, serde_json::Value>;
+
+/// Document of an application schema
+///
+/// `fields` defaults to a `FieldMap` but can be any deserializable struct.
+/// Relation fields hold the related document id (or view id for pinned relations)
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Document<T = FieldMap> {
+    pub meta: Meta,
+    pub fields: T,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Meta {
@@ -77,6 +110,8 @@ pub struct SchemaDefinitionFields {
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Fields {
+    /// Document of the `schema_field_definition_v1`
+    pub meta: Meta,
     pub fields: FieldDefinition,
 }
 
@@ -96,3 +131,9 @@ pub struct GetSchemaVars {
     pub id: String,
     pub view_id: String,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDocumentVars {
+    pub id: String,
+}
@@ -0,0 +1,323 @@
+//! Ids of schemas, operations and documents
+//!
+//! Thin wrappers around the p2panda-rs ids, so schema ids, document ids, view ids and operation ids
+//! can't be mixed up. They are (de)serialized as their string representation
+use crate::builder::fields::FieldValue;
+use crate::{Error, Result};
+
+use p2panda_rs::document;
+use p2panda_rs::operation;
+use p2panda_rs::schema::{self, error::SchemaIdError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Display;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// Implements the conversions from/to the p2panda-rs id and the string representation
+macro_rules! id_newtype {
+    ($name:ident, $inner:ty, $parse:expr) => {
+        impl $name {
+            /// The wrapped p2panda-rs id
+            pub fn as_p2panda(&self) -> &$inner {
+                &self.0
+            }
+        }
+
+        impl From<$inner> for $name {
+            fn from(id: $inner) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for $inner {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(id: &str) -> Result<Self> {
+                let parse: fn(&str) -> Result<$inner> = $parse;
+                parse(id).map(Self)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let id = String::deserialize(deserializer)?;
+                id.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+/// Id of an operation, the hash of its entry
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OperationId(operation::OperationId);
+
+id_newtype!(OperationId, operation::OperationId, |id| {
+    operation::OperationId::from_str(id).map_err(|source| Error::InvalidOperationId {
+        id: id.to_string(),
+        source,
+    })
+});
+
+impl OperationId {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Id of a document, the id of the CREATE operation
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DocumentId(document::DocumentId);
+
+id_newtype!(DocumentId, document::DocumentId, |id| {
+    document::DocumentId::from_str(id).map_err(|source| Error::InvalidDocumentId {
+        id: id.to_string(),
+        source,
+    })
+});
+
+impl DocumentId {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl From<OperationId> for DocumentId {
+    /// The document created by the operation
+    fn from(id: OperationId) -> Self {
+        Self(document::DocumentId::new(&id.0))
+    }
+}
+
+/// Id of a document view, the ids of the latest operations of the document
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DocumentViewId(document::DocumentViewId);
+
+id_newtype!(DocumentViewId, document::DocumentViewId, |id| {
+    document::DocumentViewId::from_str(id).map_err(|source| Error::InvalidViewId {
+        id: id.to_string(),
+        source,
+    })
+});
+
+impl DocumentViewId {
+    /// Ids of the operations of the view
+    pub fn operation_ids(&self) -> Vec<OperationId> {
+        self.0.iter().cloned().map(OperationId).collect()
+    }
+}
+
+impl From<OperationId> for DocumentViewId {
+    /// The view of the document right after the operation
+    fn from(id: OperationId) -> Self {
+        Self(id.0.into())
+    }
+}
+
+impl From<DocumentId> for FieldValue {
+    /// Relation to the document
+    fn from(id: DocumentId) -> Self {
+        FieldValue::Relation(id.to_string())
+    }
+}
+
+impl From<DocumentViewId> for FieldValue {
+    /// Pinned relation to the document view
+    fn from(id: DocumentViewId) -> Self {
+        FieldValue::PinnedRelation(id.0.iter().map(|id| id.to_string()).collect())
+    }
+}
+
+/// Id of a schema, `<name>_<view_id>` for application schemas or e.g. `schema_definition_v1`
+///
+/// Dereferences to the string id, so it's accepted wherever a `&str` schema id is,
+/// e.g. `FieldType::Relation(&schema_id)`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SchemaId {
+    id: String,
+    inner: schema::SchemaId,
+}
+
+impl SchemaId {
+    /// Id of the application schema `name` at the respective view of its schema definition
+    pub fn new(name: &str, view_id: &DocumentViewId) -> Result<Self> {
+        let id = schema::SchemaId::new_application(name, &view_id.0).to_string();
+        Self::check_name(name).map_err(|reason| Error::InvalidSchemaId {
+            id: id.clone(),
+            source: SchemaIdError::MalformedSchemaId(id.clone(), reason),
+        })?;
+
+        id.parse()
+    }
+
+    /// Checks the name of an application schema: 1 to 64 alphanumeric characters or `_`,
+    /// starting with a letter and not ending with `_`
+    pub fn check_name(name: &str) -> std::result::Result<(), String> {
+        let valid = name.len() <= 64
+            && name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && !name.ends_with('_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("invalid schema name '{}'", name))
+        }
+    }
+
+    /// Name of the schema, e.g. `schema_definition` for system schemas
+    pub fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// View id of the schema definition, `None` for system schemas
+    pub fn view_id(&self) -> Option<DocumentViewId> {
+        match &self.inner {
+            schema::SchemaId::Application(_, view_id) => Some(DocumentViewId(view_id.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+
+    /// The wrapped p2panda-rs id
+    pub fn as_p2panda(&self) -> &schema::SchemaId {
+        &self.inner
+    }
+}
+
+impl From<schema::SchemaId> for SchemaId {
+    fn from(inner: schema::SchemaId) -> Self {
+        Self {
+            id: inner.to_string(),
+            inner,
+        }
+    }
+}
+
+impl From<SchemaId> for schema::SchemaId {
+    fn from(id: SchemaId) -> Self {
+        id.inner
+    }
+}
+
+impl FromStr for SchemaId {
+    type Err = Error;
+
+    fn from_str(id: &str) -> Result<Self> {
+        schema::SchemaId::new(id)
+            .map(Self::from)
+            .map_err(|source| Error::InvalidSchemaId {
+                id: id.to_string(),
+                source,
+            })
+    }
+}
+
+impl Display for SchemaId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl Deref for SchemaId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.id
+    }
+}
+
+impl AsRef<str> for SchemaId {
+    fn as_ref(&self) -> &str {
+        &self.id
+    }
+}
+
+impl PartialEq<str> for SchemaId {
+    fn eq(&self, other: &str) -> bool {
+        self.id == other
+    }
+}
+
+impl PartialEq<&str> for SchemaId {
+    fn eq(&self, other: &&str) -> bool {
+        self.id == *other
+    }
+}
+
+impl Serialize for SchemaId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.id)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Document created or updated by the `Operator`
+///
+/// Returned by `create_instance` and `update_instance` and accepted by the next
+/// `update_instance` or `delete_instance` of the same document
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentHandle {
+    pub schema_id: SchemaId,
+    pub document_id: DocumentId,
+    /// View of the document after the last operation
+    pub view_id: DocumentViewId,
+}
+
+impl DocumentHandle {
+    pub fn new(schema_id: SchemaId, document_id: DocumentId, view_id: DocumentViewId) -> Self {
+        Self {
+            schema_id,
+            document_id,
+            view_id,
+        }
+    }
+
+    /// Parses the handle of a document from its ids, e.g. from the command line
+    pub fn parse(schema_id: &str, document_id: &str, view_id: &str) -> Result<Self> {
+        Ok(Self::new(
+            schema_id.parse()?,
+            document_id.parse()?,
+            view_id.parse()?,
+        ))
+    }
+
+    /// The document created by `operation_id`
+    pub(crate) fn created(schema_id: SchemaId, operation_id: OperationId) -> Self {
+        Self::new(schema_id, operation_id.clone().into(), operation_id.into())
+    }
+
+    /// The same document at the view created by `operation_id`
+    pub(crate) fn at(&self, operation_id: OperationId) -> Self {
+        Self {
+            view_id: operation_id.into(),
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Loading and storing the key pair of the `Operator`
+//!
+//! Key files contain either the hex encoded private key or, when a password is set,
+//! the private key encrypted with ChaCha20-Poly1305 under a key derived with Argon2id, whose costs
+//! are stored in the file
+use crate::{Error, Result};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use p2panda_rs::identity::KeyPair;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const KEY_FILE_VERSION: u8 = 1;
+const KDF: &str = "argon2id";
+const SALT_LEN: usize = 16;
+
+/// Argon2id costs of new key files, the defaults of argon2 0.5
+const KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: 19 * 1024,
+    t_cost: 2,
+    p_cost: 1,
+};
+
+/// Source of the key pair used by the `Operator` to sign entries
+///
+/// #### Example
+/// ```no_run
+/// # use zenode::{KeyStore, Operator};
+/// # fn main() -> zenode::Result<()> {
+/// // once
+/// KeyStore::create("key.json").password("hunter2").key_pair()?;
+///
+/// // every other time
+/// let op = Operator::builder()
+///     .key_store(KeyStore::load("key.json").password("hunter2"))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct KeyStore {
+    source: KeySource,
+    password: Option<String>,
+}
+
+#[derive(Clone)]
+enum KeySource {
+    Load(PathBuf),
+    Create(PathBuf),
+    LoadOrCreate(PathBuf),
+    Env(String),
+    Bytes(Vec<u8>),
+    Generate,
+}
+
+/// Encrypted key file
+#[derive(Serialize, Deserialize)]
+struct EncryptedKey {
+    version: u8,
+    kdf: String,
+    /// Missing in key files written before the costs were stored, those used `KDF_PARAMS`
+    #[serde(default = "default_kdf_params")]
+    kdf_params: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl KeyStore {
+    /// Loads the key file at `path`, failing if it doesn't exist
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        Self::new(KeySource::Load(path.into()))
+    }
+
+    /// Generates a new key pair and writes it to `path`, failing if the file already exists
+    ///
+    /// On unix the file is only readable and writable by its owner
+    pub fn create(path: impl Into<PathBuf>) -> Self {
+        Self::new(KeySource::Create(path.into()))
+    }
+
+    /// Loads the key file at `path` or creates it like `KeyStore::create` if it doesn't exist
+    pub fn load_or_create(path: impl Into<PathBuf>) -> Self {
+        Self::new(KeySource::LoadOrCreate(path.into()))
+    }
+
+    /// Reads the key from the environment variable `name`,
+    /// either a hex encoded private key or the content of an encrypted key file
+    pub fn from_env(name: &str) -> Self {
+        Self::new(KeySource::Env(name.to_string()))
+    }
+
+    /// Uses the 32 bytes of a private key
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self::new(KeySource::Bytes(bytes.to_vec()))
+    }
+
+    /// Generates a new key pair which only lives in memory
+    pub fn generate() -> Self {
+        Self::new(KeySource::Generate)
+    }
+
+    /// Password used to encrypt created key files and decrypt loaded ones
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Loads, creates or generates the key pair
+    pub fn key_pair(&self) -> Result<KeyPair> {
+        match &self.source {
+            KeySource::Load(path) => self.load_file(path),
+            KeySource::Create(path) => self.create_file(path),
+            KeySource::LoadOrCreate(path) => match self.load_file(path) {
+                Err(Error::KeyFile { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                    self.create_file(path)
+                }
+                result => result,
+            },
+            KeySource::Env(name) => {
+                let content =
+                    std::env::var(name).map_err(|_| Error::MissingKeyEnv(name.clone()))?;
+                self.decode(&content, &format!("${}", name))
+            }
+            KeySource::Bytes(bytes) => from_private_key(&hex::encode(bytes), "bytes"),
+            KeySource::Generate => Ok(KeyPair::new()),
+        }
+    }
+
+    fn new(source: KeySource) -> Self {
+        Self {
+            source,
+            password: None,
+        }
+    }
+
+    fn load_file(&self, path: &Path) -> Result<KeyPair> {
+        let content = fs::read_to_string(path).map_err(|source| Error::KeyFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        self.decode(&content, &path.display().to_string())
+    }
+
+    fn create_file(&self, path: &Path) -> Result<KeyPair> {
+        let key_pair = KeyPair::new();
+        let private_key = hex::encode(key_pair.private_key().to_bytes());
+
+        let content = match &self.password {
+            Some(password) => serde_json::to_string_pretty(&encrypt(&private_key, password)?)?,
+            None => private_key,
+        };
+
+        write_new(path, &content).map_err(|source| Error::KeyFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(key_pair)
+    }
+
+    /// Decodes a plain hex private key or an encrypted key file
+    fn decode(&self, content: &str, origin: &str) -> Result<KeyPair> {
+        let content = content.trim();
+
+        if !content.starts_with('{') {
+            return from_private_key(content, origin);
+        }
+
+        let encrypted: EncryptedKey = serde_json::from_str(content)?;
+        let password = self.password.as_deref().ok_or_else(|| {
+            Error::KeyEncryption(format!("{} is encrypted but no password was given", origin))
+        })?;
+
+        from_private_key(&decrypt(&encrypted, password)?, origin)
+    }
+}
+
+fn from_private_key(private_key: &str, origin: &str) -> Result<KeyPair> {
+    KeyPair::from_private_key_str(private_key).map_err(|source| Error::InvalidKey {
+        origin: origin.to_string(),
+        source,
+    })
+}
+
+/// Argon2 costs a key file was encrypted with, so it stays readable when the defaults of argon2 change
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    /// Memory in KiB
+    m_cost: u32,
+    /// Iterations
+    t_cost: u32,
+    /// Parallelism
+    p_cost: u32,
+}
+
+fn default_kdf_params() -> KdfParams {
+    KDF_PARAMS
+}
+
+fn derive_key(password: &str, salt: &[u8], params: &KdfParams) -> Result<Key> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, None)
+        .map_err(|err| Error::KeyEncryption(format!("invalid argon2 parameters: {}", err)))?;
+
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|err| Error::KeyEncryption(err.to_string()))?;
+    Ok(key)
+}
+
+fn encrypt(private_key: &str, password: &str) -> Result<EncryptedKey> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, &salt, &KDF_PARAMS)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, private_key.as_bytes())
+        .map_err(|_| Error::KeyEncryption("could not encrypt the private key".to_string()))?;
+
+    Ok(EncryptedKey {
+        version: KEY_FILE_VERSION,
+        kdf: KDF.to_string(),
+        kdf_params: KDF_PARAMS,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+fn decrypt(encrypted: &EncryptedKey, password: &str) -> Result<String> {
+    if encrypted.version != KEY_FILE_VERSION || encrypted.kdf != KDF {
+        return Err(Error::KeyEncryption(format!(
+            "unsupported key file version {} ({})",
+            encrypted.version, encrypted.kdf
+        )));
+    }
+
+    let invalid = |_| Error::KeyEncryption("malformed encrypted key".to_string());
+    let salt = hex::decode(&encrypted.salt).map_err(invalid)?;
+    let nonce = hex::decode(&encrypted.nonce).map_err(invalid)?;
+    let ciphertext = hex::decode(&encrypted.ciphertext).map_err(invalid)?;
+
+    if nonce.len() != 12 {
+        return Err(Error::KeyEncryption("malformed encrypted key".to_string()));
+    }
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(password, &salt, &encrypted.kdf_params)?);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| Error::KeyEncryption("wrong password or corrupted key".to_string()))?;
+
+    String::from_utf8(plaintext)
+        .map_err(|_| Error::KeyEncryption("wrong password or corrupted key".to_string()))
+}
+
+/// Writes a new file, failing if it exists, only accessible by the owner on unix
+fn write_new(path: &Path, content: &str) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(content.as_bytes())
+}
@@ -1,25 +1,85 @@
 pub mod builder;
+pub mod client;
+mod error;
 pub mod graphql;
+pub mod ids;
+pub mod keystore;
 mod operator;
+pub mod outbox;
+pub mod registry;
+pub mod signer;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod typed;
 mod utils;
 
-pub use builder::fields::FieldType;
+pub use builder::fields::{FieldType, FieldValue};
+pub use builder::file::SchemaFile;
+pub use builder::graph::SchemaGraph;
+pub use builder::migration::SchemaChange;
+pub use client::{GraphQLClient, NodeClient};
+pub use error::{Error, GraphQLClientError, Result};
+pub use graphql::schemas::{Document, FieldMap};
+pub use ids::{DocumentHandle, DocumentId, DocumentViewId, OperationId, SchemaId};
+pub use keystore::KeyStore;
 pub use operator::*;
+pub use outbox::{Outbox, OutboxItem, OutboxStatus};
+pub use registry::SchemaRegistry;
+pub use signer::{FileSigner, Signer};
 
 #[cfg(test)]
 mod tests {
+    use crate::builder::file::lock_path;
+    use crate::builder::migration::diff_schema;
     use crate::builder::SchemaBuilder;
-    use crate::utils::{field_to_json, sort_fields};
-    use crate::{collection_field, field, field_def, FieldType::*, Operator};
+    use crate::graphql::queries::get_document_query;
+    use crate::graphql::schemas::NextArguments;
+    use crate::graphql::schemas::{FieldDefinition, FieldMap, SchemaDefinition};
+    use crate::operator::operations::{self, operation_value};
+    use crate::operator::schema_matches;
+    #[cfg(unix)]
+    use crate::signer::SocketSigner;
+    use crate::testing::MockNode;
+    #[cfg(unix)]
+    use crate::testing::SignerDaemon;
+    use crate::utils::{flatten_relations, sort_fields};
+    use crate::{
+        collection_field, collection_list_field, field, field_def, typed_field, Document,
+        DocumentHandle, DocumentId, Error, FieldProblem, FieldTuple, FieldType, FieldType::*,
+        FieldValue, FileSigner, KeyStore, OperationId, Operator, OutboxStatus, RetryPolicy,
+        SchemaChange, SchemaGraph, SchemaId, SchemaRegistry, Signer,
+    };
+    use crate::{GraphQLClient, NodeClient};
+    use async_trait::async_trait;
+    use p2panda_rs::document::DocumentViewId;
+    use p2panda_rs::entry::encode::sign_and_encode_entry;
+    use p2panda_rs::entry::{EncodedEntry, LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{KeyPair, PublicKey};
+    use p2panda_rs::operation::decode::decode_operation;
+    use p2panda_rs::operation::encode::encode_operation;
+    use p2panda_rs::operation::plain::PlainValue;
+    use p2panda_rs::operation::traits::Schematic;
+    use p2panda_rs::operation::{EncodedOperation, OperationValue};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Starts a `MockNode` and connects an `Operator` to it, the node stops when dropped
+    async fn mock_operator() -> (MockNode, Operator) {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let op = node.operator().expect("Failed to build operator");
+        (node, op)
+    }
 
     #[tokio::test]
-    async fn create_schema_test() -> Result<(), String> {
-        let op = Operator::default();
+    async fn create_schema_test() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
 
         // ---------
         // Test create schema
 
-        let id = op
+        let schema_id = op
             .create_schema(
                 "test",
                 "DESCRIPTION",
@@ -32,9 +92,7 @@ mod tests {
             )
             .await?;
 
-        let schema_id = format!("test_{}", &id);
-
-        let instance_id = op
+        let instance = op
             .create_instance(
                 &schema_id,
                 &mut [
@@ -46,10 +104,18 @@ mod tests {
             )
             .await?;
 
-        let update_id = op
+        let instance_id = instance.document_id.as_str();
+        assert_eq!(instance.view_id.to_string(), instance_id);
+
+        // test get_document
+        let doc: Document = op.get_document(&schema_id, instance_id).await?;
+        assert_eq!(doc.meta.document_id, instance_id);
+        assert_eq!(doc.fields["name"], "UMBRA");
+        assert_eq!(doc.fields["number"], 69);
+
+        let updated = op
             .update_instance(
-                &schema_id,
-                &instance_id,
+                &instance,
                 &mut [
                     field("name", "UMBRA_BEAR"),
                     field("number", "10"),
@@ -59,10 +125,21 @@ mod tests {
             )
             .await?;
 
-        let _delete_id = op.delete_instance(&schema_id, &update_id).await?;
+        assert_eq!(updated.document_id, instance.document_id);
+        assert_ne!(updated.view_id, instance.view_id);
+
+        let doc: Document = op.get_document(&schema_id, instance_id).await?;
+        assert_eq!(doc.meta.view_id, updated.view_id.to_string());
+        assert_eq!(doc.fields["name"], "UMBRA_BEAR");
+
+        let _deleted = op.delete_instance(&updated).await?;
+
+        let res = op.get_document::<FieldMap>(&schema_id, instance_id).await;
+        assert!(matches!(res, Err(Error::DocumentNotFound(_))));
 
         // test get_schema_definition
-        let res = op.get_schema_definition(&id, &id).await;
+        let view_id = schema_id.view_id().unwrap().to_string();
+        let res = op.get_schema_definition(&view_id, &view_id).await;
         assert!(res.is_ok());
 
         // ---------
@@ -70,9 +147,30 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_error_sources() {
+        use std::error::Error as _;
+
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let err = GraphQLClient::new(node.endpoint())
+            .query("{ unknownField }", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::GraphQL(_)));
+        assert!(err.source().is_some());
+
+        // nothing listens on the discard port
+        let err = GraphQLClient::new("http://127.0.0.1:9/graphql")
+            .query("{ unknownField }", None)
+            .await
+            .unwrap_err();
+        assert!(err.is_transport());
+        assert!(err.source().is_some());
+    }
+
     #[tokio::test]
     async fn test_debug_fetch_schema() {
-        let op = Operator::default();
+        let (_node, op) = mock_operator().await;
         let res = op.get_all_schema_definition().await;
         assert!(res.is_ok(), "Should return all schema definitions");
 
@@ -81,8 +179,8 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_relation_list() -> Result<(), String> {
-        let op = Operator::default();
+    async fn test_relation_list() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
 
         let product_schema_id = op
             .create_schema(
@@ -92,8 +190,6 @@ mod tests {
             )
             .await?;
 
-        let product_schema_id = format!("product_test_{}", &product_schema_id);
-
         let client_schema_id = op
             .create_schema(
                 "client_test",
@@ -102,13 +198,11 @@ mod tests {
             )
             .await?;
 
-        let client_schema_id = format!("client_test_{}", &client_schema_id);
-
-        let product_a_id = op
+        let product_a = op
             .create_instance(&product_schema_id, &mut [field("name", "product_a")])
             .await?;
 
-        let product_b_id = op
+        let product_b = op
             .create_instance(&product_schema_id, &mut [field("name", "product_b")])
             .await?;
 
@@ -117,7 +211,10 @@ mod tests {
                 &client_schema_id,
                 &mut [collection_field(
                     "products",
-                    &[&product_a_id, &product_b_id],
+                    &[
+                        product_a.document_id.as_str(),
+                        product_b.document_id.as_str(),
+                    ],
                 )],
             )
             .await?;
@@ -126,8 +223,38 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_schema_builder() -> Result<(), String> {
-        let op = Operator::default();
+    async fn test_ensure_schema() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
+
+        let fields = || [field_def("name", Str), field_def("age", Int)];
+
+        let id = op
+            .ensure_schema("ensure_test", "Ensure test schema", &mut fields())
+            .await?;
+
+        // same fields in another order
+        let same_id = op
+            .ensure_schema(
+                "ensure_test",
+                "Ensure test schema",
+                &mut [field_def("age", Int), field_def("name", Str)],
+            )
+            .await?;
+
+        assert_eq!(id, same_id);
+
+        let other_id = op
+            .ensure_schema("ensure_test", "Another description", &mut fields())
+            .await?;
+
+        assert_ne!(id, other_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schema_builder() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
 
         let mut parent_builder = SchemaBuilder::new("parent", "PARENT TEST SCHEMA", &op)
             .field("name", Str)
@@ -143,52 +270,1616 @@ mod tests {
 
         pet_builder.build().await?;
 
-        let parent_instance_id = parent_builder
+        let parent_instance = parent_builder
             .instantiate(&mut [field("name", "Alice"), field("points", "100")])
             .await?;
 
         pet_builder
-            .instantiate(&mut [field("name", "Blue"), field("parent", &parent_instance_id)])
+            .instantiate(&mut [
+                field("name", "Blue"),
+                typed_field("parent", parent_instance.document_id),
+            ])
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schema_graph() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
+
+        let owner = SchemaBuilder::new("graph_owner", "GRAPH OWNER", &op).field("name", Str);
+        let pet = SchemaBuilder::new("graph_pet", "GRAPH PET", &op)
+            .field("name", Str)
+            .relation_to("owner", &owner)
+            .relation_list_to("friends", &owner);
+
+        // the relation target isn't built yet
+        let mut lonely =
+            SchemaBuilder::new("graph_pet", "GRAPH PET", &op).relation_to("owner", &owner);
+        assert!(matches!(lonely.build().await, Err(Error::SchemaGraph(_))));
+
+        let mut graph = SchemaGraph::new().schema(pet).schema(owner);
+        graph.build().await?;
+
+        let owner_id = graph.schema_id("graph_owner").unwrap().to_string();
+        let fields = op
+            .get_schema_fields(graph.schema_id("graph_pet").unwrap())
+            .await?;
+        let types: Vec<&str> = fields.iter().map(|f| f.type_.as_str()).collect();
+        assert!(types.contains(&format!("relation({})", owner_id).as_str()));
+        assert!(types.contains(&format!("relation_list({})", owner_id).as_str()));
+
+        // a built builder can be related to outside of a graph
+        let owner = graph.get("graph_owner").unwrap();
+        let mut toy =
+            SchemaBuilder::new("graph_toy", "GRAPH TOY", &op).pinned_relation_to("owner", owner);
+        toy.build().await?;
+
+        let schema_count = op.get_all_schema_definition().await?.all_schemas.len();
+
+        let a = SchemaBuilder::new("graph_a", "A", &op);
+        let b = SchemaBuilder::new("graph_b", "B", &op).relation_to("a", &a);
+        let a = a.relation_to("b", &b);
+        let result = SchemaGraph::new().schema(a).schema(b).build().await;
+        assert!(matches!(result, Err(Error::SchemaGraph(reason)) if reason.contains("cycle")));
+
+        // invalid names and fields of later builders are rejected before the first is published
+        let a = SchemaBuilder::new("graph_valid", "Valid", &op).field("name", Str);
+        let b = SchemaBuilder::new("graph invalid", "Invalid", &op).relation_to("a", &a);
+        let result = SchemaGraph::new().schema(a).schema(b).build().await;
+        assert!(matches!(result, Err(Error::InvalidSchemaId { .. })));
+
+        let a = SchemaBuilder::new("graph_valid", "Valid", &op).field("name", Str);
+        let b = SchemaBuilder::new("graph_empty", "Empty", &op)
+            .relation_to("a", &a)
+            .field("other", Relation(""));
+        let result = SchemaGraph::new().schema(a).schema(b).build().await;
+        assert!(matches!(result, Err(Error::SchemaGraph(reason)) if reason.contains("empty")));
+
+        // nothing was published
+        assert_eq!(
+            op.get_all_schema_definition().await?.all_schemas.len(),
+            schema_count
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_fields() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let op = node.operator()?;
+
+        let owner_id = op
+            .create_schema("owner", "Owner", &mut [field_def("name", Str)])
+            .await?;
+        let pet_id = op
+            .create_schema(
+                "validated_pet",
+                "Validated pet",
+                &mut [
+                    field_def("name", Str),
+                    field_def("age", Int),
+                    field_def("owner", Relation(&owner_id)),
+                ],
+            )
+            .await?;
+        let owner = op
+            .create_instance(&owner_id, &mut [field("name", "Alice")])
+            .await?;
+
+        // another Operator fetches the schema definition from the node
+        let other = node.operator()?;
+        let entries = node.entry_count();
+
+        let result = other
+            .create_instance(
+                &pet_id,
+                &mut [
+                    field("name", "3"),
+                    field("owner", "abc"),
+                    field("color", "red"),
+                ],
+            )
+            .await;
+
+        let problems = match result {
+            Err(Error::InvalidFields { problems, .. }) => problems,
+            _ => panic!("expected invalid fields"),
+        };
+        assert!(problems.contains(&FieldProblem::Unknown("color".to_string())));
+        assert!(problems.contains(&FieldProblem::Missing("age".to_string())));
+        assert!(problems.contains(&FieldProblem::WrongType {
+            name: "name".to_string(),
+            expected: "str".to_string(),
+            found: "int".to_string()
+        }));
+        assert!(problems.iter().any(
+            |problem| matches!(problem, FieldProblem::InvalidValue { name, .. } if name == "owner")
+        ));
+        assert_eq!(node.entry_count(), entries);
+
+        let id = other
+            .create_instance(
+                &pet_id,
+                &mut [
+                    typed_field("name", "3"),
+                    field("age", "3"),
+                    typed_field("owner", owner.document_id),
+                ],
+            )
+            .await?;
+
+        // updates don't need every field
+        other.update_instance(&id, &mut [field("age", "4")]).await?;
+        let result = other.update_instance(&id, &mut [field("age", "old")]).await;
+        assert!(matches!(result, Err(Error::InvalidFields { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_schema() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
+
+        let mut builder = SchemaBuilder::new("migration", "MIGRATION TEST SCHEMA", &op)
+            .field("name", Str)
+            .field("age", Int);
+
+        builder.build().await?;
+        let first_id = builder.schema_id.clone();
+
+        let schema_id = op
+            .update_schema(
+                &builder.operation_id,
+                &[
+                    SchemaChange::remove("age"),
+                    SchemaChange::add("weight", Float),
+                ],
+            )
+            .await?;
+
+        assert_ne!(schema_id, first_id.as_str());
+        assert_eq!(schema_id.name(), "migration");
+
+        let fields = op.get_schema_fields(&schema_id).await?;
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["name", "weight"]);
+
+        // adding an existing field is rejected
+        let result = op
+            .update_schema(&builder.operation_id, &[SchemaChange::add("name", Str)])
+            .await;
+        assert!(matches!(result, Err(Error::InvalidSchemaChange(_))));
+
+        let mut builder = SchemaBuilder::new("migration", "MIGRATION TEST SCHEMA", &op)
+            .field("name", Str)
+            .field("weight", Float)
+            .field("shiny", Bool);
+
+        builder.migrate(&schema_id).await?;
+        assert_ne!(builder.schema_id, schema_id.as_str());
+
+        // nothing changed, the schema id stays the same
+        let migrated_id = builder.schema_id.clone();
+        builder.migrate(&migrated_id).await?;
+        assert_eq!(builder.schema_id, migrated_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schema_file() -> Result<(), Error> {
+        let (_node, op) = mock_operator().await;
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("zenode_schemas_{}.toml", std::process::id()));
+        let lock_path = lock_path(&path);
+
+        // pet is declared before the owner it relates to
+        std::fs::write(
+            &path,
+            r#"
+            [[schemas]]
+            name = "file_pet"
+            description = "Pet with an owner"
+            fields = { name = "str", owner = "relation(file_owner)" }
+
+            [[schemas]]
+            name = "file_owner"
+            description = "Pet owner"
+            fields = { name = "str", age = "int" }
+            "#,
+        )
+        .unwrap();
+
+        let schema_ids = op.apply_schema_file(&path).await?;
+        let owner_id = &schema_ids["file_owner"];
+        let fields = op.get_schema_fields(&schema_ids["file_pet"]).await?;
+        let owner = fields.iter().find(|f| f.name == "owner").unwrap();
+        assert_eq!(owner.type_, format!("relation({})", owner_id));
+
+        let lock = std::fs::read_to_string(&lock_path).unwrap();
+        assert!(lock.contains(owner_id.as_str()));
+
+        // applying again reuses the schemas
+        assert_eq!(op.apply_schema_file(&path).await?, schema_ids);
+
+        let cycle_path = dir.join(format!("zenode_schemas_{}.yaml", std::process::id()));
+        std::fs::write(
+            &cycle_path,
+            "schemas:\n  - name: a\n    fields: { b: relation(b) }\n  - name: b\n    fields: { a: relation_list(a) }\n",
+        )
+        .unwrap();
+        let result = op.apply_schema_file(&cycle_path).await;
+        assert!(
+            matches!(result, Err(Error::SchemaFile { reason, .. }) if reason.contains("cycle"))
+        );
+
+        // schemas applied before a failure are recorded in the lockfile
+        let failing_path = dir.join(format!("zenode_failing_{}.toml", std::process::id()));
+        std::fs::write(
+            &failing_path,
+            r#"
+            [[schemas]]
+            name = "file_applied"
+            description = "Applied before the failure"
+            fields = { name = "str" }
+
+            [[schemas]]
+            name = "file invalid"
+            description = "Invalid name"
+            fields = { name = "str" }
+            "#,
+        )
+        .unwrap();
+        let failing_lock_path = failing_path.with_extension("toml.lock");
+        assert!(op.apply_schema_file(&failing_path).await.is_err());
+        let lock = std::fs::read_to_string(&failing_lock_path).unwrap();
+        assert!(lock.contains("file_applied"));
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&lock_path).ok();
+        std::fs::remove_file(&cycle_path).ok();
+        std::fs::remove_file(&failing_lock_path).ok();
+        std::fs::remove_file(&failing_path).ok();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_node_verifies_entries() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let client = gql_client::Client::new(node.endpoint());
+        let key_pair = KeyPair::new();
+
+        let publish = |entry: String, operation: String| {
+            let client = &client;
+            async move {
+                let query = format!(
+                    r#"mutation {{ publish(entry: "{}", operation: "{}") {{ logId seqNum }} }}"#,
+                    entry, operation
+                );
+                client.query_unwrap::<serde_json::Value>(&query).await
+            }
+        };
+
+        let operation = encode_operation(&operations::schema_field_definition(&field_def(
+            "name", Str,
+        ))?)?;
+        let other = encode_operation(&operations::schema_field_definition(&field_def(
+            "age", Int,
+        ))?)?;
+
+        let sign = |operation| {
+            sign_and_encode_entry(
+                &LogId::default(),
+                &SeqNum::default(),
+                None,
+                None,
+                operation,
+                &key_pair,
+            )
+        };
+
+        // the entry was signed for another operation
+        let res = publish(sign(&other)?.to_string(), operation.to_string()).await;
+        assert!(res.is_err());
+        assert_eq!(node.entry_count(), 0);
+
+        let entry = sign(&operation)?.to_string();
+        publish(entry.clone(), operation.to_string()).await?;
+        assert_eq!(node.entry_count(), 1);
+
+        // seq num 1 was already published in this log
+        let res = publish(entry.clone(), operation.to_string()).await;
+        assert!(res.is_err());
+        assert_eq!(node.entry_count(), 1);
+
+        // which the GraphQLClient reports as a log conflict
+        let res = GraphQLClient::new(node.endpoint())
+            .publish(&sign(&operation)?, &operation)
+            .await;
+        assert!(matches!(res, Err(Error::LogConflict(_))));
+
+        Ok(())
+    }
+
+    /// Forwards every call to a `GraphQLClient` and records the called methods
+    struct RecordingClient {
+        inner: GraphQLClient,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl NodeClient for RecordingClient {
+        async fn next_args(
+            &self,
+            public_key: &PublicKey,
+            view_id: Option<&DocumentViewId>,
+        ) -> Result<NextArguments, Error> {
+            self.calls.lock().unwrap().push("next_args");
+            self.inner.next_args(public_key, view_id).await
+        }
+
+        async fn publish(
+            &self,
+            entry: &EncodedEntry,
+            operation: &EncodedOperation,
+        ) -> Result<NextArguments, Error> {
+            self.calls.lock().unwrap().push("publish");
+            self.inner.publish(entry, operation).await
+        }
+
+        async fn query(
+            &self,
+            query: &str,
+            variables: Option<serde_json::Value>,
+        ) -> Result<serde_json::Value, Error> {
+            self.calls.lock().unwrap().push("query");
+            self.inner.query(query, variables).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_client() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let op = Operator::builder()
+            .client(RecordingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                calls: calls.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "client_test",
+                "Client test schema",
+                &mut [field_def("name", Str)],
+            )
+            .await?;
+        let view_id = schema_id.view_id().unwrap().to_string();
+        op.get_schema_definition(&view_id, &view_id).await?;
+
+        // the second entry follows the publish response of the first one
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["next_args", "publish", "publish", "query"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_next_args() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let private_key = KeyPair::new().private_key().to_bytes();
+
+        let op = Operator::builder()
+            .client(RecordingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                calls: calls.clone(),
+            })
+            .key_store(KeyStore::from_bytes(&private_key))
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "logs_test",
+                "Logs test schema",
+                &mut [field_def("name", Str)],
+            )
+            .await?;
+
+        let instance = op
+            .create_instance(&schema_id, &mut [typed_field("name", "a")])
+            .await?;
+        let updated = op
+            .update_instance(&instance, &mut [typed_field("name", "b")])
+            .await?;
+        op.update_instance(&updated, &mut [typed_field("name", "c")])
+            .await?;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "next_args",
+                "publish",
+                "publish",
+                "publish",
+                "publish",
+                "publish"
+            ]
+        );
+
+        // another operator with the same key takes the next free log on the node
+        Operator::builder()
+            .endpoint(node.endpoint())
+            .key_store(KeyStore::from_bytes(&private_key))
+            .build()?
+            .create_instance(&schema_id, &mut [typed_field("name", "d")])
+            .await?;
+
+        calls.lock().unwrap().clear();
+        op.create_instance(&schema_id, &mut [typed_field("name", "e")])
+            .await?;
+
+        // the node rejects the cached arguments, they are fetched again
+        assert_eq!(*calls.lock().unwrap(), ["publish", "next_args", "publish"]);
+        assert_eq!(node.entry_count(), 7);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_as_author() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let op = Operator::builder()
+            .client(RecordingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                calls: calls.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "author_test",
+                "Author test schema",
+                &mut [field_def("name", Str)],
+            )
+            .await?;
+
+        let bob = KeyPair::new();
+        let as_bob = op.as_author(&bob);
+        assert_eq!(as_bob.public_key(), bob.public_key());
+        assert_ne!(as_bob.public_key(), op.public_key());
+
+        // the schema registered by the first author is shared
+        let bobs = as_bob
+            .create_instance("author_test", &mut [typed_field("name", "bob")])
+            .await?;
+        assert_eq!(bobs.schema_id, schema_id);
+        let own = op
+            .create_instance(&schema_id, &mut [typed_field("name", "own")])
+            .await?;
+        as_bob
+            .update_instance(&bobs, &mut [typed_field("name", "bobby")])
+            .await?;
+        op.update_instance(&own, &mut [typed_field("name", "owned")])
+            .await?;
+
+        // the logs of every author are tracked separately, bob only asks for his first log
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "next_args",
+                "publish",
+                "publish",
+                "next_args",
+                "publish",
+                "publish",
+                "publish",
+                "publish"
+            ]
+        );
+
+        let doc: Document = as_bob
+            .get_document(&schema_id, bobs.document_id.as_str())
+            .await?;
+        assert_eq!(doc.fields["name"], "bobby");
+        assert_eq!(node.entry_count(), 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch() -> Result<(), Error> {
+        use futures::StreamExt;
+        use tokio::time::timeout;
+
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let op = Operator::builder()
+            .endpoint(node.endpoint())
+            .key_store(KeyStore::generate())
+            .watch_interval(Duration::from_millis(20))
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "watch_test",
+                "Watch test schema",
+                &mut [field_def("name", Str)],
+            )
+            .await?;
+        let first = op
+            .create_instance(&schema_id, &mut [typed_field("name", "a")])
+            .await?;
+
+        let wait = Duration::from_secs(5);
+        let document_id = first.document_id.as_str();
+        let mut document = Box::pin(op.watch_document::<FieldMap>(&schema_id, document_id));
+        let mut schema = Box::pin(op.watch_schema::<FieldMap>(&schema_id));
+
+        let view = timeout(wait, document.next()).await.unwrap().unwrap()?;
+        assert_eq!(view.meta.view_id, first.view_id.to_string());
+        let view = timeout(wait, schema.next()).await.unwrap().unwrap()?;
+        assert_eq!(view.meta.document_id, document_id);
+
+        // unchanged views are polled a few times but never yielded again
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let update = op
+            .update_instance(&first, &mut [typed_field("name", "b")])
+            .await?;
+
+        let view = timeout(wait, document.next()).await.unwrap().unwrap()?;
+        assert_eq!(view.meta.view_id, update.view_id.to_string());
+        assert_eq!(view.fields["name"], "b");
+
+        let second = op
+            .create_instance(&schema_id, &mut [typed_field("name", "c")])
+            .await?;
+
+        let mut changed = Vec::new();
+        while changed.len() < 2 {
+            let view = timeout(wait, schema.next()).await.unwrap().unwrap()?;
+            changed.push(view.meta.view_id);
+        }
+        changed.sort();
+        let mut expected = vec![update.view_id.to_string(), second.view_id.to_string()];
+        expected.sort();
+        assert_eq!(changed, expected);
+
+        op.delete_instance(&update).await?;
+        let deleted = timeout(wait, document.next()).await.unwrap().unwrap();
+        assert!(matches!(deleted, Err(Error::DocumentNotFound(_))));
+        assert!(timeout(wait, document.next()).await.unwrap().is_none());
+
+        // a zero interval is raised to the minimum instead of polling in a busy loop
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let op = Operator::builder()
+            .client(RecordingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                calls: calls.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .watch_interval(Duration::ZERO)
+            .watch_max_backoff(Duration::ZERO)
+            .build()?;
+        let mut schema = Box::pin(op.watch_schema::<FieldMap>(&schema_id));
+        let _ = timeout(Duration::from_millis(200), schema.next()).await;
+        let _ = timeout(Duration::from_millis(200), schema.next()).await;
+        assert!(calls.lock().unwrap().len() < 30);
+
+        Ok(())
+    }
+
+    /// Signs every entry with a fixed signature of the given size
+    struct BadSigner(PublicKey, usize);
+
+    #[async_trait]
+    impl Signer for BadSigner {
+        fn public_key(&self) -> PublicKey {
+            self.0
+        }
+
+        async fn sign(&self, _bytes: &[u8]) -> Result<Vec<u8>, Error> {
+            Ok(vec![7; self.1])
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_signer() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let path = std::env::temp_dir().join(format!("zenode_signer_{}.key", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let key_pair = KeyStore::create(&path).key_pair()?;
+        let daemon = SignerDaemon::start(FileSigner::new(KeyStore::load(&path))?)
+            .await
+            .expect("Failed to start signer daemon");
+
+        let signer = SocketSigner::connect(daemon.path()).await?;
+        assert_eq!(signer.public_key(), key_pair.public_key());
+
+        let op = Operator::builder()
+            .endpoint(node.endpoint())
+            .signer(signer)
+            .build()?;
+        assert_eq!(op.public_key(), key_pair.public_key());
+
+        // the node verifies the signatures of the daemon
+        let schema_id = op
+            .create_schema(
+                "signer_test",
+                "Signer test schema",
+                &mut [field_def("name", Str)],
+            )
             .await?;
+        op.create_instance(&schema_id, &mut [typed_field("name", "signed")])
+            .await?;
+        assert_eq!(node.entry_count(), 3);
+
+        // invalid signatures never reach the node
+        for size in [64, 0, 63, 65] {
+            let bad = op.with_signer(BadSigner(key_pair.public_key(), size));
+            let result = bad
+                .create_instance(&schema_id, &mut [typed_field("name", "bad")])
+                .await;
+            assert!(matches!(result, Err(Error::Signer(_))));
+        }
+        assert_eq!(node.entry_count(), 3);
+
+        drop(daemon);
+        assert!(SocketSigner::connect(&path.with_extension("sock"))
+            .await
+            .is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_publish_batch() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let op = Operator::builder()
+            .client(RecordingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                calls: calls.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .batch_concurrency(3)
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "batch_test",
+                "Batch test schema",
+                &mut [field_def("name", Str), field_def("number", Int)],
+            )
+            .await?;
+        calls.lock().unwrap().clear();
+
+        let mut instances: Vec<Vec<FieldTuple>> = (0..20)
+            .map(|i| {
+                vec![
+                    typed_field("name", format!("item {}", i)),
+                    typed_field("number", i),
+                ]
+            })
+            .collect();
+        instances[5].push(typed_field("name", "duplicate"));
+
+        let results = op.create_instances(&schema_id, instances).await;
+
+        assert_eq!(results.len(), 20);
+        assert!(matches!(results[5], Err(Error::InvalidFieldValue { .. })));
+        assert_eq!(results.iter().filter(|res| res.is_ok()).count(), 19);
+
+        // every new log id was handed out locally
+        assert!(!calls.lock().unwrap().contains(&"next_args"));
+
+        let docs: Vec<Document> = op.get_all_documents(&schema_id).await?;
+        assert_eq!(docs.len(), 19);
+
+        // operations on the same document are published one after the other
+        let first = results[0].as_ref().unwrap();
+        let second = results[1].as_ref().unwrap();
+
+        let results = op
+            .publish_batch(vec![
+                operations::update(
+                    &schema_id,
+                    first.view_id.as_p2panda(),
+                    &[typed_field("number", 100)],
+                )?,
+                operations::delete(&schema_id, second.view_id.as_p2panda())?,
+                operations::update(
+                    &schema_id,
+                    first.view_id.as_p2panda(),
+                    &[typed_field("number", 200)],
+                )?,
+            ])
+            .await;
+
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        let doc: Document = op
+            .get_document(&schema_id, first.document_id.as_str())
+            .await?;
+        assert_eq!(doc.fields["number"], 200);
+
+        let docs: Vec<Document> = op.get_all_documents(&schema_id).await?;
+        assert_eq!(docs.len(), 18);
+
+        Ok(())
+    }
+
+    /// Fails the next publishes with the given errors, then forwards them to a `GraphQLClient`
+    struct FlakyClient {
+        inner: GraphQLClient,
+        failures: Arc<Mutex<Vec<Error>>>,
+        publishes: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl NodeClient for FlakyClient {
+        async fn next_args(
+            &self,
+            public_key: &PublicKey,
+            view_id: Option<&DocumentViewId>,
+        ) -> Result<NextArguments, Error> {
+            self.inner.next_args(public_key, view_id).await
+        }
+
+        async fn publish(
+            &self,
+            entry: &EncodedEntry,
+            operation: &EncodedOperation,
+        ) -> Result<NextArguments, Error> {
+            *self.publishes.lock().unwrap() += 1;
+            let failure = self.failures.lock().unwrap().pop();
+            match failure {
+                Some(err) => Err(err),
+                None => self.inner.publish(entry, operation).await,
+            }
+        }
+
+        async fn query(
+            &self,
+            query: &str,
+            variables: Option<serde_json::Value>,
+        ) -> Result<serde_json::Value, Error> {
+            self.inner.query(query, variables).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let publishes = Arc::new(Mutex::new(0));
+
+        let op = Operator::builder()
+            .client(FlakyClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                failures: failures.clone(),
+                publishes: publishes.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .retry_policy(
+                RetryPolicy::default()
+                    .max_attempts(3)
+                    .initial_backoff(Duration::from_millis(1)),
+            )
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "retry_test",
+                "Retry test schema",
+                &mut [field_def("name", Str)],
+            )
+            .await?;
+
+        let fail = |errors: Vec<Error>| {
+            *failures.lock().unwrap() = errors;
+            *publishes.lock().unwrap() = 0;
+        };
+
+        // transport errors and seq num conflicts are retried with new arguments
+        fail(vec![
+            Error::LogConflict("seq num 2 was taken".to_string()),
+            Error::ClientTransport("connection reset".to_string()),
+        ]);
+        let instance = op
+            .create_instance(&schema_id, &mut [typed_field("name", "a")])
+            .await?;
+        assert_eq!(*publishes.lock().unwrap(), 3);
+
+        // other rejections of the node are returned right away
+        fail(vec![Error::ClientResponse(
+            "operation does not match schema".to_string(),
+        )]);
+        let res = op
+            .update_instance(&instance, &mut [typed_field("name", "b")])
+            .await;
+        assert!(matches!(res, Err(Error::ClientResponse(_))));
+        assert_eq!(*publishes.lock().unwrap(), 1);
+
+        // so are invalid fields, before anything is published
+        fail(Vec::new());
+        let res = op
+            .update_instance(&instance, &mut [typed_field("name", 1)])
+            .await;
+        assert!(matches!(res, Err(Error::InvalidFields { .. })));
+        assert_eq!(*publishes.lock().unwrap(), 0);
+
+        // the last error is returned once every attempt failed
+        fail(
+            (0..3)
+                .map(|_| Error::ClientTransport("connection refused".to_string()))
+                .collect(),
+        );
+        let res = op
+            .update_instance(&instance, &mut [typed_field("name", "c")])
+            .await;
+        assert!(matches!(res, Err(Error::ClientTransport(_))));
+        assert_eq!(*publishes.lock().unwrap(), 3);
+
+        fail(Vec::new());
+        op.update_instance(&instance, &mut [typed_field("name", "d")])
+            .await?;
+        assert_eq!(node.entry_count(), 4);
+
+        let policy = RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(300))
+            .jitter(0.5);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        let delay = policy.delay(2);
+        assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+
+        let none = RetryPolicy::none();
+        assert!(!none.should_retry(1, &Error::ClientTransport(String::new())));
+        assert_eq!(RetryPolicy::default(), none);
+
+        // invalid jitter never panics
+        for jitter in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            RetryPolicy::default().jitter(jitter).delay(1);
+        }
+
+        Ok(())
+    }
+
+    /// Forwards every call to a `GraphQLClient` while `online` is set
+    struct SwitchClient {
+        inner: GraphQLClient,
+        online: Arc<AtomicBool>,
+    }
+
+    impl SwitchClient {
+        fn check(&self) -> Result<(), Error> {
+            if self.online.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(Error::ClientTransport("offline".to_string()))
+            }
+        }
+    }
+
+    #[async_trait]
+    impl NodeClient for SwitchClient {
+        async fn next_args(
+            &self,
+            public_key: &PublicKey,
+            view_id: Option<&DocumentViewId>,
+        ) -> Result<NextArguments, Error> {
+            self.check()?;
+            self.inner.next_args(public_key, view_id).await
+        }
+
+        async fn publish(
+            &self,
+            entry: &EncodedEntry,
+            operation: &EncodedOperation,
+        ) -> Result<NextArguments, Error> {
+            self.check()?;
+            self.inner.publish(entry, operation).await
+        }
+
+        async fn query(
+            &self,
+            query: &str,
+            variables: Option<serde_json::Value>,
+        ) -> Result<serde_json::Value, Error> {
+            self.check()?;
+            self.inner.query(query, variables).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let path = std::env::temp_dir().join(format!("zenode_outbox_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let online = Arc::new(AtomicBool::new(true));
+        let private_key = KeyPair::new().private_key().to_bytes();
+
+        let schema_id = node
+            .operator()?
+            .create_schema(
+                "outbox_test",
+                "Outbox test schema",
+                &mut [field_def("number", Int)],
+            )
+            .await?;
+        let entries = node.entry_count();
+
+        let build = || {
+            Operator::builder()
+                .client(SwitchClient {
+                    inner: GraphQLClient::new(node.endpoint()),
+                    online: online.clone(),
+                })
+                .key_store(KeyStore::from_bytes(&private_key))
+                .validate_fields(false)
+                .retry_policy(RetryPolicy::none())
+                .outbox(path.clone())
+                .build()
+        };
+        let op = build()?;
+
+        // the first entry of the author asks the node for the next free log
+        let first = op
+            .create_instance(&schema_id, &mut [typed_field("number", 0)])
+            .await?;
+        online.store(false, Ordering::SeqCst);
+
+        // everything else is signed offline, long enough for skiplinks
+        let mut handle = first.clone();
+        for i in 1..=13 {
+            handle = op
+                .update_instance(&handle, &mut [typed_field("number", i)])
+                .await?;
+        }
+        let second = op
+            .create_instance(&schema_id, &mut [typed_field("number", 100)])
+            .await?;
+        op.delete_instance(&second).await?;
+        assert_eq!(node.entry_count(), entries);
+
+        // the outbox survives a restart, flushing waits for the node
+        drop(op);
+        let op = build()?;
+        assert_eq!(op.outbox_items().await?.len(), 16);
+
+        let report = op.flush().await?;
+        assert_eq!(report.len(), 16);
+        assert!(report
+            .iter()
+            .all(|item| item.status == OutboxStatus::Pending));
+
+        online.store(true, Ordering::SeqCst);
+        let report = op.flush().await?;
+        assert_eq!(report[0].operation_id, first.view_id.operation_ids()[0]);
+        assert_eq!(report[15].seq_num, 2);
+        assert!(report
+            .iter()
+            .all(|item| item.status == OutboxStatus::Published));
+        assert!(op.outbox_items().await?.is_empty());
+        assert_eq!(node.entry_count(), entries + 16);
+
+        let doc: Document = op
+            .get_document(&schema_id, first.document_id.as_str())
+            .await?;
+        assert_eq!(doc.fields["number"], 13);
+        assert_eq!(doc.meta.view_id, handle.view_id.to_string());
+
+        // a rejected entry holds back the rest of its log
+        online.store(false, Ordering::SeqCst);
+        let invalid = op
+            .create_instance(&schema_id, &mut [typed_field("unknown", 1)])
+            .await?;
+        op.update_instance(&invalid, &mut [typed_field("number", 1)])
+            .await?;
+        let handle = op
+            .update_instance(&handle, &mut [typed_field("number", 14)])
+            .await?;
+
+        online.store(true, Ordering::SeqCst);
+        let report = op.flush().await?;
+        assert!(matches!(report[0].status, OutboxStatus::Failed(_)));
+        assert_eq!(report[1].status, OutboxStatus::Pending);
+        assert_eq!(report[2].status, OutboxStatus::Published);
+        assert_eq!(op.outbox_items().await?.len(), 2);
+
+        // discarding removes the rejected entry and the rest of its log
+        let discarded = op.discard_failed().await?;
+        assert_eq!(discarded.len(), 2);
+        assert!(op.outbox_items().await?.is_empty());
+
+        // the discarded log was never created, the next new log is asked from the node again
+        op.create_instance(&schema_id, &mut [typed_field("number", 200)])
+            .await?;
+
+        // an entry the node holds already, e.g. after a lost flush response, counts as published
+        op.update_instance(&handle, &mut [typed_field("number", 15)])
+            .await?;
+        let items = op.outbox_items().await?;
+        let (entry, operation) = items[1].decode()?;
+        GraphQLClient::new(node.endpoint())
+            .publish(&entry, &operation)
+            .await?;
+
+        let report = op.flush().await?;
+        assert!(report
+            .iter()
+            .all(|item| item.status == OutboxStatus::Published));
+        assert!(op.outbox_items().await?.is_empty());
+        let doc: Document = op
+            .get_document(&schema_id, first.document_id.as_str())
+            .await?;
+        assert_eq!(doc.fields["number"], 15);
+
+        // without an outbox there is nothing to flush
+        assert!(matches!(
+            node.operator()?.flush().await,
+            Err(Error::Outbox(_))
+        ));
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_offline_validation() -> Result<(), Error> {
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let path = std::env::temp_dir().join(format!(
+            "zenode_outbox_validation_{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let online = Arc::new(AtomicBool::new(true));
+        let private_key = KeyPair::new().private_key().to_bytes();
+
+        let node_schema_id = node
+            .operator()?
+            .create_schema(
+                "node_schema",
+                "Schema the outbox doesn't know",
+                &mut [field_def("number", Int)],
+            )
+            .await?;
+
+        let build = || {
+            Operator::builder()
+                .client(SwitchClient {
+                    inner: GraphQLClient::new(node.endpoint()),
+                    online: online.clone(),
+                })
+                .key_store(KeyStore::from_bytes(&private_key))
+                .outbox(path.clone())
+                .build()
+        };
+
+        // the definitions of schemas created with an outbox are kept in the outbox file
+        let schema_id = build()?
+            .create_schema(
+                "outbox_schema",
+                "Outbox schema",
+                &mut [field_def("number", Int)],
+            )
+            .await?;
+        online.store(false, Ordering::SeqCst);
+
+        // so a fresh Operator validates offline
+        let op = build()?;
+        op.create_instance(&schema_id, &mut [typed_field("number", 1)])
+            .await?;
+        let res = op
+            .create_instance(&schema_id, &mut [typed_field("unknown", 1)])
+            .await;
+        assert!(matches!(res, Err(Error::InvalidFields { .. })));
+
+        // unknown schemas are validated by the node once it's reachable
+        op.create_instance(&node_schema_id, &mut [typed_field("number", 2)])
+            .await?;
+        assert_eq!(op.outbox_items().await?.len(), 4);
+
+        online.store(true, Ordering::SeqCst);
+        let report = op.flush().await?;
+        assert!(report
+            .iter()
+            .all(|item| item.status == OutboxStatus::Published));
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    /// Never answers `next_args` while `hang` is set
+    struct HangingClient {
+        inner: GraphQLClient,
+        hang: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl NodeClient for HangingClient {
+        async fn next_args(
+            &self,
+            public_key: &PublicKey,
+            view_id: Option<&DocumentViewId>,
+        ) -> Result<NextArguments, Error> {
+            if self.hang.load(Ordering::SeqCst) {
+                std::future::pending::<()>().await;
+            }
+            self.inner.next_args(public_key, view_id).await
+        }
+
+        async fn publish(
+            &self,
+            entry: &EncodedEntry,
+            operation: &EncodedOperation,
+        ) -> Result<NextArguments, Error> {
+            self.inner.publish(entry, operation).await
+        }
+
+        async fn query(
+            &self,
+            query: &str,
+            variables: Option<serde_json::Value>,
+        ) -> Result<serde_json::Value, Error> {
+            self.inner.query(query, variables).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox_slow_node() -> Result<(), Error> {
+        use tokio::time::timeout;
+
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let path =
+            std::env::temp_dir().join(format!("zenode_outbox_slow_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let hang = Arc::new(AtomicBool::new(false));
+
+        let op = Operator::builder()
+            .client(HangingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                hang: hang.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .outbox(path.clone())
+            .build()?;
+        let schema_id = op
+            .create_schema("slow_test", "Slow test", &mut [field_def("number", Int)])
+            .await?;
+
+        // an author unknown to the outbox waits for the node without locking the outbox
+        hang.store(true, Ordering::SeqCst);
+        let other = op.as_author(&KeyPair::new());
+        let schema = schema_id.clone();
+        let waiting = tokio::spawn(async move {
+            other
+                .create_instance(&schema, &mut [typed_field("number", 1)])
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let wait = Duration::from_secs(5);
+        timeout(
+            wait,
+            op.create_instance(&schema_id, &mut [typed_field("number", 2)]),
+        )
+        .await
+        .expect("enqueue blocked by a slow node")?;
+        let report = timeout(wait, op.flush())
+            .await
+            .expect("flush blocked by a slow node")?;
+        assert_eq!(report.len(), 3);
+
+        waiting.abort();
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_publish() -> Result<(), Error> {
+        fn shareable<T: Clone + Send + Sync + 'static>() {}
+        shareable::<Operator>();
+
+        let node = MockNode::start().await.expect("Failed to start mock node");
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let op = Operator::builder()
+            .client(RecordingClient {
+                inner: GraphQLClient::new(node.endpoint()),
+                calls: calls.clone(),
+            })
+            .key_store(KeyStore::generate())
+            .build()?;
+
+        let schema_id = op
+            .create_schema(
+                "concurrent_test",
+                "Concurrent test schema",
+                &mut [field_def("number", Int)],
+            )
+            .await?;
+
+        // every task writes its own log, entries of the same log wait for each other
+        let tasks: Vec<_> = (0..10)
+            .map(|i| {
+                let op = op.clone();
+                let schema_id = schema_id.clone();
+                tokio::spawn(async move {
+                    let handle = op
+                        .create_instance(&schema_id, &mut [typed_field("number", i)])
+                        .await?;
+                    let handle = op
+                        .update_instance(&handle, &mut [typed_field("number", i * 10)])
+                        .await?;
+                    op.update_instance(&handle, &mut [typed_field("number", i * 100)])
+                        .await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.expect("Task panicked")?;
+        }
+
+        // no publish was rejected for a seq num or log id conflict
+        let publishes = calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| **call == "publish")
+            .count();
+        assert_eq!(node.entry_count(), 2 + 10 * 3);
+        assert_eq!(publishes, node.entry_count());
+
+        let docs: Vec<Document> = op.get_all_documents(&schema_id).await?;
+        assert_eq!(docs.len(), 10);
 
         Ok(())
     }
 
     #[test]
-    fn test_field_to_json() {
+    fn test_ids() -> Result<(), Error> {
+        let hash = Hash::new_from_bytes(&[1, 2, 3]).to_string();
+        let other = Hash::new_from_bytes(&[4, 5, 6]).to_string();
+
+        let operation_id: OperationId = hash.parse()?;
+        let document_id = DocumentId::from(operation_id.clone());
+        let view_id = crate::DocumentViewId::from(operation_id.clone());
+        assert_eq!(document_id.as_str(), hash);
+        assert_eq!(view_id.to_string(), hash);
+        assert_eq!(view_id.operation_ids(), [operation_id]);
+
+        let view_id: crate::DocumentViewId = format!("{}_{}", hash, other).parse()?;
+        assert_eq!(view_id.operation_ids().len(), 2);
         assert_eq!(
-            field_to_json(&field("name", "false")),
-            String::from(r#""name": false"#)
+            FieldValue::from(view_id.clone()),
+            FieldValue::PinnedRelation(vec![hash.clone(), other.clone()])
         );
+        assert_eq!(
+            FieldValue::from(document_id.clone()),
+            FieldValue::Relation(hash.clone())
+        );
+
+        assert!(matches!(
+            "abc".parse::<DocumentId>(),
+            Err(Error::InvalidDocumentId { .. })
+        ));
+        assert!(matches!(
+            "abc".parse::<OperationId>(),
+            Err(Error::InvalidOperationId { .. })
+        ));
+        let schema_id = SchemaId::new("pet", &view_id)?;
+        assert_eq!(schema_id.to_string(), format!("pet_{}_{}", hash, other));
+        assert_eq!(schema_id.name(), "pet");
+        assert_eq!(schema_id.view_id(), Some(view_id.clone()));
+        assert_eq!(schema_id.as_str().parse::<SchemaId>()?, schema_id);
+
+        let system: SchemaId = "schema_definition_v1".parse()?;
+        assert_eq!(system.name(), "schema_definition");
+        assert_eq!(system.view_id(), None);
 
+        assert!(matches!(
+            "pet".parse::<SchemaId>(),
+            Err(Error::InvalidSchemaId { .. })
+        ));
+        assert!(matches!(
+            SchemaId::new("1pet", &view_id),
+            Err(Error::InvalidSchemaId { .. })
+        ));
+        assert!(DocumentHandle::parse("schema", &hash, &hash).is_err());
+        assert!(DocumentHandle::parse(&schema_id, &hash, "abc").is_err());
+
+        // handles are (de)serialized with the string ids
+        let handle = DocumentHandle::parse(&schema_id, &hash, &view_id.to_string())?;
+        let json = serde_json::to_value(&handle)?;
+        assert_eq!(json["schema_id"], schema_id.as_str());
+        assert_eq!(json["document_id"], hash);
+        assert_eq!(json["view_id"], format!("{}_{}", hash, other));
+        assert_eq!(serde_json::from_value::<DocumentHandle>(json)?, handle);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_guess() {
+        assert_eq!(field("name", "false").1, FieldValue::Bool(false));
+        assert_eq!(field("name", "Bob").1, FieldValue::Str("Bob".to_string()));
+        assert_eq!(field("number", "1000").1, FieldValue::Int(1000));
+        assert_eq!(field("float", "387.927").1, FieldValue::Float(387.927));
+        assert_eq!(field("float", "40.00000").1, FieldValue::Float(40.0));
+        assert_eq!(field("int", "0000099").1, FieldValue::Int(99));
         assert_eq!(
-            field_to_json(&field("name", "Bob")),
-            String::from(r#""name": "Bob""#)
+            field("vec", r#"["id_020208973fb0"]"#).1,
+            FieldValue::RelationList(vec!["id_020208973fb0".to_string()])
         );
+    }
+
+    #[test]
+    fn test_operation_value() {
+        let id = Hash::new_from_bytes(&[1, 2, 3]).to_string();
 
         assert_eq!(
-            field_to_json(&field("number", "1000")),
-            String::from(r#""number": 1000"#)
+            operation_value("name", &typed_field("name", "true").1).unwrap(),
+            OperationValue::String("true".to_string())
         );
 
         assert_eq!(
-            field_to_json(&field("float", "387.927")),
-            String::from(r#""float": 387.927"#)
+            operation_value("code", &typed_field("code", "0000099").1).unwrap(),
+            OperationValue::String("0000099".to_string())
         );
 
         assert_eq!(
-            field_to_json(&field("float", "40.00000")),
-            String::from(r#""float": 40.0"#)
+            operation_value("number", &typed_field("number", 99).1).unwrap(),
+            OperationValue::Integer(99)
         );
 
         assert_eq!(
-            field_to_json(&field("int", "0000099")),
-            String::from(r#""int": 99"#)
+            operation_value("isFree", &typed_field("isFree", false).1).unwrap(),
+            OperationValue::Boolean(false)
         );
 
         assert_eq!(
-            field_to_json(&field("vec", r#"["id_020208973fb0"]"#)),
-            String::from(r#""vec": ["id_020208973fb0"]"#)
+            operation_value("pinned", &collection_list_field("pinned", &[&id]).1)
+                .unwrap()
+                .field_type(),
+            "pinned_relation_list"
+        );
+
+        assert!(operation_value("nan", &FieldValue::Float(f64::NAN)).is_err());
+        assert!(operation_value("rel", &FieldValue::Relation("not_a_hash".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_operation_round_trip() {
+        let description = "A \"quoted\" description,\nwith a \\ backslash\", \"name\": \"injected";
+
+        let operation = operations::schema_definition("test", description, &[]).unwrap();
+        let encoded = encode_operation(&operation).unwrap();
+        let decoded = decode_operation(&encoded).unwrap();
+
+        let fields = decoded.fields().unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(
+            fields.get("description"),
+            Some(&PlainValue::StringOrRelation(description.to_string()))
+        );
+        assert_eq!(
+            fields.get("name"),
+            Some(&PlainValue::StringOrRelation("test".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_schema_registry() -> Result<(), Error> {
+        let path =
+            std::env::temp_dir().join(format!("zenode_registry_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut registry = SchemaRegistry::open(&path)?;
+        registry.insert("pet", "pet_0020aa", "Pet schema", &[field_def("name", Str)])?;
+        registry.insert("pet", "pet_0020aa", "Pet schema", &[field_def("name", Str)])?;
+        assert_eq!(registry.get("pet").unwrap().version, 1);
+
+        registry.insert(
+            "pet",
+            "pet_0020bb",
+            "Pet schema",
+            &[field_def("name", Str), field_def("age", Int)],
+        )?;
+
+        // reload from disk
+        let registry = SchemaRegistry::open(&path)?;
+        let pet = registry.get("pet").unwrap();
+        assert_eq!(pet.schema_id, "pet_0020bb");
+        assert_eq!(pet.version, 2);
+        assert_eq!(pet.fields[0], field_def("age", Int));
+
+        assert_eq!(registry.resolve("pet"), "pet_0020bb");
+        assert_eq!(registry.resolve("dog_0020cc"), "dog_0020cc");
+
+        std::fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_store() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("zenode_key_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // loading never creates the file
+        assert!(matches!(
+            KeyStore::load(&path).key_pair(),
+            Err(Error::KeyFile { .. })
+        ));
+        assert!(!path.exists());
+
+        let key_pair = KeyStore::create(&path).password("hunter2").key_pair()?;
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(&hex::encode(key_pair.private_key().to_bytes())));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // an existing key is never overwritten
+        assert!(matches!(
+            KeyStore::create(&path).key_pair(),
+            Err(Error::KeyFile { .. })
+        ));
+
+        let loaded = KeyStore::load(&path).password("hunter2").key_pair()?;
+        assert_eq!(loaded.public_key(), key_pair.public_key());
+
+        // the argon2 costs are stored with the key, files without them use the costs of argon2 0.5
+        let mut json: serde_json::Value = serde_json::from_str(&content)?;
+        assert_eq!(
+            json["kdf_params"],
+            serde_json::json!({ "m_cost": 19456, "t_cost": 2, "p_cost": 1 })
+        );
+        json.as_object_mut().unwrap().remove("kdf_params");
+        let legacy_path = path.with_extension("legacy");
+        std::fs::write(&legacy_path, json.to_string()).unwrap();
+        let legacy = KeyStore::load(&legacy_path)
+            .password("hunter2")
+            .key_pair()?;
+        assert_eq!(legacy.public_key(), key_pair.public_key());
+        std::fs::remove_file(&legacy_path).unwrap();
+
+        let loaded = KeyStore::load_or_create(&path)
+            .password("hunter2")
+            .key_pair()?;
+        assert_eq!(loaded.public_key(), key_pair.public_key());
+
+        assert!(matches!(
+            KeyStore::load(&path).password("wrong").key_pair(),
+            Err(Error::KeyEncryption(_))
+        ));
+        assert!(matches!(
+            KeyStore::load(&path).key_pair(),
+            Err(Error::KeyEncryption(_))
+        ));
+
+        let bytes = key_pair.private_key().to_bytes();
+        let from_bytes = KeyStore::from_bytes(&bytes).key_pair()?;
+        assert_eq!(from_bytes.public_key(), key_pair.public_key());
+        assert!(matches!(
+            KeyStore::from_bytes(&[1, 2, 3]).key_pair(),
+            Err(Error::InvalidKey { .. })
+        ));
+
+        std::env::set_var("ZENODE_TEST_KEY", content);
+        let from_env = KeyStore::from_env("ZENODE_TEST_KEY")
+            .password("hunter2")
+            .key_pair()?;
+        assert_eq!(from_env.public_key(), key_pair.public_key());
+        assert!(matches!(
+            KeyStore::from_env("ZENODE_TEST_MISSING_KEY").key_pair(),
+            Err(Error::MissingKeyEnv(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_matches() {
+        let schema: SchemaDefinition = serde_json::from_str(
+            r#"{
+                "meta": { "documentId": "0020aa", "viewId": "0020aa" },
+                "fields": {
+                    "name": "pet",
+                    "description": "Pet schema",
+                    "fields": [
+                        {
+                            "meta": { "documentId": "0020bb", "viewId": "0020bb" },
+                            "fields": { "name": "name", "type": "str" }
+                        },
+                        {
+                            "meta": { "documentId": "0020cc", "viewId": "0020cc" },
+                            "fields": { "name": "age", "type": "int" }
+                        }
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let fields = [field_def("age", Int), field_def("name", Str)];
+
+        assert!(schema_matches(&schema, "pet", "Pet schema", &fields));
+        assert!(!schema_matches(&schema, "dog", "Pet schema", &fields));
+        assert!(!schema_matches(&schema, "pet", "Dog schema", &fields));
+        assert!(!schema_matches(&schema, "pet", "Pet schema", &fields[..1]));
+        assert!(!schema_matches(
+            &schema,
+            "pet",
+            "Pet schema",
+            &[field_def("age", Float), field_def("name", Str)]
+        ));
+    }
+
+    #[test]
+    fn test_diff_schema() {
+        let schema: SchemaDefinition = serde_json::from_str(
+            r#"{
+                "meta": { "documentId": "0020aa", "viewId": "0020aa" },
+                "fields": {
+                    "name": "pet",
+                    "description": "Pet schema",
+                    "fields": [
+                        {
+                            "meta": { "documentId": "0020bb", "viewId": "0020bb" },
+                            "fields": { "name": "name", "type": "str" }
+                        },
+                        {
+                            "meta": { "documentId": "0020cc", "viewId": "0020cc" },
+                            "fields": { "name": "age", "type": "int" }
+                        }
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let fields = [field_def("age", Int), field_def("name", Str)];
+        assert!(diff_schema(&schema, "Pet schema", &fields).is_empty());
+
+        let changes = diff_schema(
+            &schema,
+            "Dog schema",
+            &[field_def("age", Float), field_def("owner", Str)],
+        );
+
+        assert_eq!(
+            changes,
+            [
+                SchemaChange::Description("Dog schema".to_string()),
+                SchemaChange::remove("name"),
+                SchemaChange::remove("age"),
+                SchemaChange::add("age", Float),
+                SchemaChange::add("owner", Str),
+            ]
         );
     }
 
@@ -237,5 +1928,63 @@ mod tests {
             PinnedRelationList("schema_02020fb20").to_string(),
             "pinned_relation_list(schema_02020fb20)"
         );
+
+        let parsed = FieldType::parse("relation_list(schema_02020fb20)").unwrap();
+        assert_eq!(parsed.target(), Some("schema_02020fb20"));
+        assert_eq!(parsed.with_target("pet").to_string(), "relation_list(pet)");
+        assert_eq!(FieldType::parse("int").unwrap().to_string(), "int");
+        assert!(FieldType::parse("relation(schema").is_none());
+        assert!(FieldType::parse("list(schema)").is_none());
+    }
+
+    #[test]
+    fn test_document_query() {
+        let definitions = [
+            FieldDefinition {
+                name: "name".to_string(),
+                type_: "str".to_string(),
+            },
+            FieldDefinition {
+                name: "parent".to_string(),
+                type_: "relation(parent_0020aa)".to_string(),
+            },
+        ];
+
+        let query = get_document_query("pet_0020bb", &definitions);
+
+        assert!(query.contains("document: pet_0020bb(id: $id)"));
+        assert!(query.contains("fields { name parent { meta { documentId viewId } } }"));
+    }
+
+    #[test]
+    fn test_flatten_relations() {
+        let definitions = [
+            FieldDefinition {
+                name: "parent".to_string(),
+                type_: "relation(parent_0020aa)".to_string(),
+            },
+            FieldDefinition {
+                name: "toys".to_string(),
+                type_: "pinned_relation_list(toy_0020cc)".to_string(),
+            },
+        ];
+
+        let mut fields: FieldMap = serde_json::from_str(
+            r#"{
+                "name": "Blue",
+                "parent": { "meta": { "documentId": "doc_a", "viewId": "view_a" } },
+                "toys": [
+                    { "meta": { "documentId": "doc_b", "viewId": "view_b" } },
+                    { "meta": { "documentId": "doc_c", "viewId": "view_c" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        flatten_relations(&mut fields, &definitions);
+
+        assert_eq!(fields["name"], "Blue");
+        assert_eq!(fields["parent"], "doc_a");
+        assert_eq!(fields["toys"], serde_json::json!(["view_b", "view_c"]));
     }
 }
@@ -0,0 +1,120 @@
+use crate::graphql::schemas::NextArguments;
+
+use p2panda_rs::document::DocumentViewId;
+use p2panda_rs::entry::{LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use std::collections::HashMap;
+
+/// Arguments for the next entry of every log written by the `Operator`
+///
+/// Seeded with `nextArgs` and kept up to date from the `publish` responses,
+/// so most operations are published without asking the node first
+#[derive(Debug, Default)]
+pub(crate) struct LogCache {
+    authors: HashMap<String, AuthorLogs>,
+}
+
+#[derive(Debug, Default)]
+struct AuthorLogs {
+    /// Next free log id, `None` until it's known from the node
+    next_log_id: Option<LogId>,
+    /// Log of every operation published by the author or used as `previous`
+    operations: HashMap<String, LogId>,
+    /// Arguments for the next entry of every log of the author
+    logs: HashMap<LogId, NextArguments>,
+}
+
+impl LogCache {
+    /// Returns the arguments for the next entry of an operation based on `previous`,
+    /// `None` if they are unknown and have to be fetched from the node
+    ///
+    /// A new log id is reserved for operations without `previous`
+    pub fn next_args(
+        &mut self,
+        public_key: &str,
+        previous: Option<&DocumentViewId>,
+    ) -> Option<NextArguments> {
+        let author = self.authors.get_mut(public_key)?;
+
+        match previous {
+            Some(view_id) => {
+                let log_id = view_id
+                    .iter()
+                    .find_map(|id| author.operations.get(id.as_str()))?;
+                author.logs.get(log_id).cloned()
+            }
+            None => {
+                let log_id = author.next_log_id?;
+                author.next_log_id = Some(LogId::new(log_id.as_u64() + 1));
+
+                Some(NextArguments {
+                    log_id,
+                    seq_num: SeqNum::default(),
+                    skiplink: None,
+                    backlink: None,
+                })
+            }
+        }
+    }
+
+    /// Returns the log of the author the document with the respective view id is written to
+    pub fn log_id(&self, public_key: &str, view_id: &DocumentViewId) -> Option<LogId> {
+        let author = self.authors.get(public_key)?;
+        view_id
+            .iter()
+            .find_map(|id| author.operations.get(id.as_str()))
+            .copied()
+    }
+
+    /// Returns `true` if new log ids can be handed out without asking the node
+    pub fn knows_next_log_id(&self, public_key: &str) -> bool {
+        self.authors
+            .get(public_key)
+            .is_some_and(|author| author.next_log_id.is_some())
+    }
+
+    /// Records a published entry and the arguments for the next entry in the same log
+    ///
+    /// Forgets the logs of the author instead if `next` doesn't follow `used`
+    pub fn published(
+        &mut self,
+        public_key: &str,
+        previous: Option<&DocumentViewId>,
+        operation_id: &Hash,
+        used: &NextArguments,
+        next: NextArguments,
+    ) {
+        let follows =
+            next.log_id == used.log_id && next.seq_num.as_u64() == used.seq_num.as_u64() + 1;
+
+        if !follows {
+            self.forget(public_key);
+            return;
+        }
+
+        let author = self.authors.entry(public_key.to_string()).or_default();
+
+        // The node hands out new logs in order, so the next free log id follows the new one
+        if used.seq_num.is_first() {
+            let after = LogId::new(used.log_id.as_u64() + 1);
+            author.next_log_id = Some(author.next_log_id.map_or(after, |id| Ord::max(id, after)));
+        }
+
+        author
+            .operations
+            .insert(operation_id.as_str().to_string(), used.log_id);
+
+        for id in previous.into_iter().flat_map(|view_id| view_id.iter()) {
+            author
+                .operations
+                .insert(id.as_str().to_string(), used.log_id);
+        }
+
+        author.logs.insert(used.log_id, next);
+    }
+
+    /// Forgets the logs of the author, the next operations fetch their arguments from the node
+    pub fn forget(&mut self, public_key: &str) {
+        self.authors.remove(public_key);
+    }
+}
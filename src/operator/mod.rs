@@ -1,87 +1,155 @@
-use crate::builder::fields::FieldType;
+use crate::builder::fields::{FieldType, FieldValue};
+use crate::builder::migration::SchemaChange;
+use crate::client::{GraphQLClient, NodeClient};
 use crate::graphql::{self, schemas::*};
+use crate::ids::{DocumentHandle, OperationId, SchemaId};
+use crate::keystore::KeyStore;
+use crate::outbox::Outbox;
+use crate::registry::SchemaRegistry;
+use crate::signer::{sign_entry, Signer};
+use crate::typed::{self, Schema};
 use crate::utils::*;
+use crate::{Error, Result};
+use logs::LogCache;
+use queue::PublishQueue;
 
-use gql_client::Client;
+use futures::stream::{self, StreamExt};
+use p2panda_rs::schema::error::SchemaIdError;
 use p2panda_rs::{
     self,
-    entry::{encode::sign_and_encode_entry, traits::AsEncodedEntry},
-    identity::KeyPair,
-    operation::{encode::encode_plain_operation, plain::PlainOperation, traits::Actionable},
+    entry::traits::AsEncodedEntry,
+    identity::{KeyPair, PublicKey},
+    operation::{encode::encode_operation, traits::Actionable, EncodedOperation, Operation},
 };
-use std::fmt::{Debug, Display};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
-#[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
-#[repr(u8)]
-enum OperationAction {
-    Create = 0,
-    Update = 1,
-    Delete = 2,
-}
+mod logs;
+mod offline;
+pub(crate) mod operations;
+mod queue;
+mod retry;
+mod validation;
+mod watch;
 
-impl Display for OperationAction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", *self as usize)
-    }
-}
+pub use retry::RetryPolicy;
+pub use validation::FieldProblem;
 
 pub type StringTuple = (String, String);
 
-pub fn field(a: &str, b: &str) -> StringTuple {
-    (a.to_string(), b.to_string())
+/// Field name and value of a document
+pub type FieldTuple = (String, FieldValue);
+
+/// Utility function to define a document field, inferring the type of the value from the string
+///
+/// Kept for compatibility, prefer `typed_field` which encodes the value exactly as given.
+/// See `FieldValue::guess` for the inference rules
+pub fn field(a: &str, b: &str) -> FieldTuple {
+    (a.to_string(), FieldValue::guess(b))
 }
 
-/// Utility function to define a schema field
+/// Utility function to define a document field
 ///
-/// `field_def("number", FieldType::Int)` is equivalent to `field("number", "int")`
+/// `typed_field("name", "true")` is a string, `typed_field("isFree", true)` is a bool
+pub fn typed_field(name: &str, value: impl Into<FieldValue>) -> FieldTuple {
+    (name.to_string(), value.into())
+}
+
+/// Fails with `Error::InvalidSchemaId` if `name` can't be the name of a schema
+pub(crate) fn check_schema_name(name: &str) -> Result<()> {
+    SchemaId::check_name(name).map_err(|reason| Error::InvalidSchemaId {
+        id: name.to_string(),
+        source: SchemaIdError::MalformedSchemaId(name.to_string(), reason),
+    })
+}
+
+/// Utility function to define a schema field
 ///
-/// A subtle difference is that `FieldType::Relation(<id>)` is converted to `"relation(<id>)"`.
+/// `field_def("number", FieldType::Int)` returns `("number", "int")`.
+/// `FieldType::Relation(<id>)` is converted to `"relation(<id>)"`.
 /// The same applies to `RelationList, PinnedRelation, PinnedRelationList`
 pub fn field_def(name: &str, field_type: FieldType) -> StringTuple {
     (name.to_string(), field_type.to_string())
 }
 
-/// Utility function to create a relation_list/pinned_relation
+/// Utility function to create a relation_list
 ///
-/// Transforms the `ids` to a string in the shape: `[id, id, ...]`
-pub fn collection_field(name: &str, ids: &[&str]) -> StringTuple {
-    let vec: Vec<String> = ids.iter().map(|x| format!("\"{}\"", x)).collect();
-    let j = vec.join(", ");
-    (name.to_string(), format!("[{}]", j))
+/// Encoded in the shape: `[id, id, ...]`, which is also the shape of a pinned_relation
+pub fn collection_field(name: &str, ids: &[&str]) -> FieldTuple {
+    let ids = ids.iter().map(|x| x.to_string()).collect();
+    (name.to_string(), FieldValue::RelationList(ids))
 }
 
 /// Utility function to create a Pinned relation list
 ///
-/// Transforms the `ids` to a string in the shape: `[[id], [id], ...]`
-pub fn collection_list_field(name: &str, ids: &[&str]) -> StringTuple {
-    let vec: Vec<String> = ids.iter().map(|x| format!("[\"{}\"]", x)).collect();
-    let j = vec.join(", ");
-    (name.to_string(), format!("[{}]", j))
+/// Encoded in the shape: `[[id], [id], ...]`
+pub fn collection_list_field(name: &str, ids: &[&str]) -> FieldTuple {
+    let ids = ids.iter().map(|x| vec![x.to_string()]).collect();
+    (name.to_string(), FieldValue::PinnedRelationList(ids))
 }
 
 const DEFAULT_ENDPOINT: &str = "http://localhost:2020/graphql";
+const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_WATCH_INTERVAL: Duration = Duration::from_millis(10);
+const DEFAULT_WATCH_MAX_BACKOFF: Duration = Duration::from_secs(30);
+const DEFAULT_KEY_PATH: &str = "key.txt";
+const PRIVATE_KEY_ENV: &str = "PRIVATE_KEY";
 
+/// Publishes operations to a p2panda node and queries its documents
+///
+/// Other identities share the transport, registry and log state through `Operator::as_author`.
+/// Cloning is cheap and the clones share all state, so an Operator can be used from many tasks at once,
+/// e.g. in the state of a web server. Entries of the same log are published one after the other
+#[derive(Clone)]
 pub struct Operator {
-    version: usize,
-    key_pair: KeyPair,
-    client: Client,
+    signer: Arc<dyn Signer>,
+    client: Arc<dyn NodeClient>,
+    registry: Arc<Mutex<SchemaRegistry>>,
+    logs: Arc<Mutex<LogCache>>,
+    queue: Arc<PublishQueue>,
+    /// Entries signed offline, `None` unless `OperatorBuilder::outbox` is set
+    outbox: Option<Arc<tokio::sync::Mutex<Outbox>>>,
+    /// Field names and types by schema id, for the client side validation
+    definitions: Arc<Mutex<HashMap<String, Vec<StringTuple>>>>,
+    validate: bool,
+    retry: RetryPolicy,
+    batch_concurrency: usize,
+    watch_interval: Duration,
+    watch_max_backoff: Duration,
 }
 
 /// Utility Struct to build an Operator
 /// #### Example
-/// ```
+/// ```no_run
+/// # use std::path::PathBuf;
+/// # use zenode::{KeyStore, Operator};
+/// # fn main() -> zenode::Result<()> {
 /// let op = Operator::builder()
 ///   .version(1)
-///   .key_pair_path(PathBuf::from("key.txt"))
+///   .key_store(KeyStore::load("key.txt"))
 ///   .endpoint("http://localhost:2020/graphql")
-///   .build();
+///   .registry_path(PathBuf::from("schemas.json"))
+///   .build()?;
+/// # Ok(())
+/// # }
 /// ```
 pub struct OperatorBuilder {
     version: usize,
-    key_pair_path: Option<PathBuf>,
+    key_store: KeyStore,
+    signer: Option<Arc<dyn Signer>>,
     endpoint: String,
+    client: Option<Arc<dyn NodeClient>>,
+    registry_path: Option<PathBuf>,
+    outbox_path: Option<PathBuf>,
+    validate: bool,
+    retry: RetryPolicy,
+    batch_concurrency: usize,
+    watch_interval: Duration,
+    watch_max_backoff: Duration,
 }
 
 impl Default for OperatorBuilder {
@@ -94,8 +162,17 @@ impl OperatorBuilder {
     pub fn new() -> Self {
         OperatorBuilder {
             version: 1,
-            key_pair_path: None,
+            key_store: KeyStore::load(DEFAULT_KEY_PATH),
+            signer: None,
             endpoint: DEFAULT_ENDPOINT.to_string(),
+            client: None,
+            registry_path: None,
+            outbox_path: None,
+            validate: true,
+            retry: RetryPolicy::none(),
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
+            watch_interval: DEFAULT_WATCH_INTERVAL,
+            watch_max_backoff: DEFAULT_WATCH_MAX_BACKOFF,
         }
     }
 
@@ -104,8 +181,21 @@ impl OperatorBuilder {
         self
     }
 
+    /// Loads the key pair from an existing key file, same as `key_store(KeyStore::load(path))`
     pub fn key_pair_path(mut self, path: PathBuf) -> Self {
-        self.key_pair_path = Some(path);
+        self.key_store = KeyStore::load(path);
+        self
+    }
+
+    /// Source of the key pair, by default the existing key file `key.txt`
+    pub fn key_store(mut self, key_store: KeyStore) -> Self {
+        self.key_store = key_store;
+        self
+    }
+
+    /// Signs the entries with a custom `Signer` instead of a key pair of the `KeyStore`
+    pub fn signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
         self
     }
 
@@ -114,18 +204,127 @@ impl OperatorBuilder {
         self
     }
 
-    pub fn build(self) -> Operator {
+    /// Uses a custom `NodeClient` instead of the `GraphQLClient` of the endpoint
+    pub fn client(mut self, client: impl NodeClient + 'static) -> Self {
+        self.client = Some(Arc::new(client));
+        self
+    }
+
+    /// Persists the `SchemaRegistry` to a json file, by default the registry only lives in memory
+    pub fn registry_path(mut self, path: PathBuf) -> Self {
+        self.registry_path = Some(path);
+        self
+    }
+
+    /// Signs the entries offline and queues them in a json file instead of publishing them,
+    /// until they're published with `Operator::flush`
+    ///
+    /// The log state is kept in the same file, only the first entry in a log unknown to
+    /// the outbox needs the node. Queries still go to the node
+    pub fn outbox(mut self, path: PathBuf) -> Self {
+        self.outbox_path = Some(path);
+        self
+    }
+
+    /// Validates the fields of `create_instance` and `update_instance` against the schema
+    /// before signing, enabled by default
+    ///
+    /// The schema definition is fetched once per schema id, schemas created by the Operator are known already.
+    /// With an outbox the definitions are kept in the outbox file, fields of schemas the outbox doesn't
+    /// know are left to the node while it can't be reached
+    pub fn validate_fields(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Retries publishing after transport errors and log conflicts, see `RetryPolicy`
+    ///
+    /// By default an operation is published once, errors are returned right away
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Maximum number of logs `Operator::publish_batch` publishes to at once, 4 by default
+    pub fn batch_concurrency(mut self, concurrency: usize) -> Self {
+        self.batch_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Time between two polls of `Operator::watch_document` and `watch_schema`, 1 second by default
+    ///
+    /// Intervals shorter than 10 milliseconds are raised to 10 milliseconds
+    pub fn watch_interval(mut self, interval: Duration) -> Self {
+        self.watch_interval = interval.max(MIN_WATCH_INTERVAL);
+        self
+    }
+
+    /// Longest time between two polls after failed ones, 30 seconds by default
+    ///
+    /// The time between polls doubles with every failed poll in a row
+    pub fn watch_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.watch_max_backoff = max_backoff;
+        self
+    }
+
+    /// Builds the Operator, failing if the key pair can't be loaded
+    pub fn build(self) -> Result<Operator> {
         let Self {
             version,
-            key_pair_path,
+            key_store,
+            signer,
             endpoint,
+            client,
+            registry_path,
+            outbox_path,
+            validate,
+            retry,
+            batch_concurrency,
+            watch_interval,
+            watch_max_backoff,
         } = self;
 
-        Operator {
-            version,
-            key_pair: get_key_pair(key_pair_path),
-            client: Client::new(endpoint),
+        // p2panda-rs only knows about operations of version 1
+        if version != 1 {
+            return Err(Error::UnsupportedVersion(version));
         }
+
+        let registry = match registry_path {
+            Some(path) => SchemaRegistry::open(path)?,
+            None => SchemaRegistry::in_memory(),
+        };
+
+        let outbox = match outbox_path {
+            Some(path) => Some(Arc::new(tokio::sync::Mutex::new(Outbox::open(path)?))),
+            None => None,
+        };
+
+        let signer = match signer {
+            Some(signer) => signer,
+            None => Arc::new(key_store.key_pair()?),
+        };
+
+        Ok(Operator {
+            signer,
+            client: client.unwrap_or_else(|| Arc::new(GraphQLClient::new(&endpoint))),
+            registry: Arc::new(Mutex::new(registry)),
+            logs: Arc::new(Mutex::new(LogCache::default())),
+            queue: Arc::new(PublishQueue::default()),
+            outbox,
+            definitions: Arc::new(Mutex::new(HashMap::new())),
+            validate,
+            retry,
+            batch_concurrency,
+            watch_interval,
+            watch_max_backoff,
+        })
+    }
+}
+
+impl Default for Operator {
+    /// Same as `Operator::from_env()` but panics if the key pair can't be loaded
+    fn default() -> Self {
+        Self::from_env().expect("Could not create the default Operator")
     }
 }
 
@@ -136,8 +335,9 @@ impl Operator {
     }
 
     /// Creates a new Operator with default values
-    /// `version: 1, path: "key.txt", endpoint: ENDPOINT env variable or if unset "http://localhost:2020/graphql"`
-    pub fn default() -> Self {
+    /// `version: 1, key: PRIVATE_KEY env variable or if unset the existing "key.txt",
+    /// endpoint: ENDPOINT env variable or if unset "http://localhost:2020/graphql"`
+    pub fn from_env() -> Result<Self> {
         let endpoint = std::env::var("ENDPOINT").ok();
         let mut op = Operator::builder();
 
@@ -145,276 +345,740 @@ impl Operator {
             op = op.endpoint(&endpoint);
         }
 
+        if std::env::var_os(PRIVATE_KEY_ENV).is_some() {
+            op = op.key_store(KeyStore::from_env(PRIVATE_KEY_ENV));
+        }
+
         op.build()
     }
 
+    /// Returns an Operator publishing as `key_pair`, see `Operator::with_signer`
+    pub fn as_author(&self, key_pair: &KeyPair) -> Operator {
+        // Unwrap as the private key comes from a valid key pair
+        let key_pair = KeyPair::from_private_key(key_pair.private_key()).unwrap();
+        self.with_signer(key_pair)
+    }
+
+    /// Returns an Operator publishing as `signer`
+    ///
+    /// Both Operators share the transport, the `SchemaRegistry`, the log state, the publish queue
+    /// and the outbox, which are tracked per public key
+    pub fn with_signer(&self, signer: impl Signer + 'static) -> Operator {
+        Operator {
+            signer: Arc::new(signer),
+            client: self.client.clone(),
+            registry: self.registry.clone(),
+            logs: self.logs.clone(),
+            queue: self.queue.clone(),
+            outbox: self.outbox.clone(),
+            definitions: self.definitions.clone(),
+            validate: self.validate,
+            retry: self.retry,
+            batch_concurrency: self.batch_concurrency,
+            watch_interval: self.watch_interval,
+            watch_max_backoff: self.watch_max_backoff,
+        }
+    }
+
+    /// Public key of the author of the published entries
+    pub fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+
+    /// Returns the `SchemaRegistry` with the schemas created by this Operator
+    pub fn registry(&self) -> MutexGuard<'_, SchemaRegistry> {
+        // The registry is always left in a consistent state, so a poisoned lock is still usable
+        self.registry.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Records a schema in the `SchemaRegistry` and remembers its fields for the validation
+    pub(crate) async fn record_schema(
+        &self,
+        name: &str,
+        schema_id: &str,
+        description: &str,
+        fields: &[StringTuple],
+    ) -> Result<()> {
+        self.record_definitions(schema_id, fields).await?;
+        self.registry().insert(name, schema_id, description, fields)
+    }
+
+    /// Remembers the fields of a schema for the validation, in the outbox as well if there is one
+    pub(crate) async fn record_definitions(
+        &self,
+        schema_id: &str,
+        fields: &[StringTuple],
+    ) -> Result<()> {
+        self.definitions()
+            .insert(schema_id.to_string(), fields.to_vec());
+
+        match &self.outbox {
+            Some(outbox) => outbox.lock().await.record_schema(schema_id, fields),
+            None => Ok(()),
+        }
+    }
+
+    fn definitions(&self) -> MutexGuard<'_, HashMap<String, Vec<StringTuple>>> {
+        self.definitions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the schema id registered with `name_or_id` or `name_or_id` itself
+    pub fn resolve_schema_id(&self, name_or_id: &str) -> String {
+        self.registry().resolve(name_or_id).to_string()
+    }
+
     /// Creates a schema by first publishing the fields, retrieving the field ids
     /// and publishing the schema with the field ids
+    ///
+    /// Returns the schema id `<name>_<operation_id>`, which is also recorded in the `SchemaRegistry`.
+    /// The name is checked before anything is published
     pub async fn create_schema(
         &self,
         name: &str,
         description: &str,
         fields: &mut [StringTuple],
-    ) -> Result<String, String> {
+    ) -> Result<SchemaId> {
+        check_schema_name(name)?;
+
         // publish fields to node and retrieve field_ids
         let field_ids = self.publish_fields(fields).await?;
 
         // create schema with field_ids
-        self.publish_schema(name, description, &field_ids).await
+        let id = self.publish_schema(name, description, &field_ids).await?;
+
+        let schema_id = SchemaId::new(name, &id.into())?;
+        self.record_schema(name, &schema_id, description, fields)
+            .await?;
+
+        Ok(schema_id)
     }
 
-    /// Publishes the schema definition to the node
-    async fn publish_schema(
+    /// Returns the id of a schema on the node with the same name, description and fields
+    /// or creates the schema if there is none. The fields are compared by name and type, ignoring order.
+    ///
+    /// Like `create_schema` it returns the schema id
+    pub async fn ensure_schema(
         &self,
         name: &str,
         description: &str,
-        field_ids: &[String],
-    ) -> Result<String, String> {
-        let field_content: String = field_ids
+        fields: &mut [StringTuple],
+    ) -> Result<SchemaId> {
+        sort_fields(fields);
+
+        let all = self.get_all_schema_definition().await?;
+        let existing = all
+            .all_schemas
             .iter()
-            .map(|it| format!("[\"{}\"]", it))
-            .collect::<Vec<_>>()
-            .join(", ");
-        /*
-            Fields should have the following shape:
-            "fields": [
-              ["<field_id>"],
-              ["<field_id>"]
-            ],
-        */
-        let json = format!(
-            r#"[{}, {}, "schema_definition_v1", {{ "description": "{}", "fields": [{}], "name": "{}" }}]"#,
-            self.version,
-            OperationAction::Create,
-            description,
-            field_content,
-            name
+            .find(|schema| schema_matches(schema, name, description, fields));
+
+        match existing {
+            Some(schema) => {
+                let schema_id = SchemaId::new(name, &schema.meta.view_id.parse()?)?;
+                self.record_schema(name, &schema_id, description, fields)
+                    .await?;
+                Ok(schema_id)
+            }
+            None => self.create_schema(name, description, fields).await,
+        }
+    }
+
+    /// Publishes a new version of the schema with the respective `schema_view_id`
+    /// by sending an UPDATE of its `schema_definition_v1` document
+    ///
+    /// Only the added fields are published as new `schema_field_definition_v1` documents.
+    /// Returns the new schema id (`<name>_<operation_id>`), which is also recorded in the `SchemaRegistry`
+    pub async fn update_schema(
+        &self,
+        schema_view_id: &str,
+        changes: &[SchemaChange],
+    ) -> Result<SchemaId> {
+        let current = self.find_schema_definition(schema_view_id).await?;
+        let name = current.fields.name.clone();
+        let mut description = current.fields.description.clone();
+
+        // (name, type, field definition view id) of the fields kept in the new version
+        let mut kept: Vec<(String, String, String)> = current
+            .fields
+            .fields
+            .into_iter()
+            .map(|f| (f.fields.name, f.fields.type_, f.meta.view_id))
+            .collect();
+        let mut added: Vec<StringTuple> = Vec::new();
+
+        // Removals first, so a field can be removed and added again with another type
+        for change in changes {
+            if let SchemaChange::RemoveField(field_name) = change {
+                let len = kept.len();
+                kept.retain(|(n, _, _)| n != field_name);
+                if kept.len() == len {
+                    return Err(Error::InvalidSchemaChange(format!(
+                        "field '{}' does not exist",
+                        field_name
+                    )));
+                }
+            }
+        }
+
+        for change in changes {
+            match change {
+                SchemaChange::AddField(field_name, field_type) => {
+                    let exists = kept.iter().any(|(n, _, _)| n == field_name)
+                        || added.iter().any(|(n, _)| n == field_name);
+                    if exists {
+                        return Err(Error::InvalidSchemaChange(format!(
+                            "field '{}' already exists",
+                            field_name
+                        )));
+                    }
+                    added.push((field_name.clone(), field_type.clone()));
+                }
+                SchemaChange::Description(d) => description = d.clone(),
+                SchemaChange::RemoveField(_) => {}
+            }
+        }
+
+        let added_ids = self.publish_fields(&mut added).await?;
+
+        let mut fields: Vec<(String, String, String)> = kept;
+        fields.extend(
+            added
+                .into_iter()
+                .zip(added_ids)
+                .map(|((n, t), id)| (n, t, id)),
         );
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
 
-        self.send_to_node(&json).await
+        let field_ids: Vec<String> = fields.iter().map(|(_, _, id)| id.clone()).collect();
+        let operation =
+            operations::schema_definition_update(schema_view_id, &description, &field_ids)?;
+        let id = self.send_to_node(&operation).await?;
+
+        let schema_id = SchemaId::new(&name, &id.into())?;
+        let field_defs: Vec<StringTuple> = fields.into_iter().map(|(n, t, _)| (n, t)).collect();
+        self.record_schema(&name, &schema_id, &description, &field_defs)
+            .await?;
+
+        Ok(schema_id)
+    }
+
+    /// Publishes the schema definition to the node
+    async fn publish_schema(
+        &self,
+        name: &str,
+        description: &str,
+        field_ids: &[String],
+    ) -> Result<OperationId> {
+        let operation = operations::schema_definition(name, description, field_ids)?;
+        self.send_to_node(&operation).await
     }
 
     /// Publishes the field definitions to the node
-    async fn publish_fields(&self, fields: &mut [StringTuple]) -> Result<Vec<String>, String> {
+    async fn publish_fields(&self, fields: &mut [StringTuple]) -> Result<Vec<String>> {
         sort_fields(fields);
 
         let mut field_ids: Vec<String> = Vec::with_capacity(fields.len());
 
-        for (name, f_type) in fields.iter() {
-            let json = format!(
-                r#"[{}, {}, "schema_field_definition_v1", {{ "name": "{}", "type": "{}" }}]"#,
-                self.version,
-                OperationAction::Create,
-                name,
-                f_type
-            );
-
-            let id = self.send_to_node(&json).await?;
-            field_ids.push(id);
+        for field in fields.iter() {
+            let operation = operations::schema_field_definition(field)?;
+            let id = self.send_to_node(&operation).await?;
+            field_ids.push(id.to_string());
         }
 
         Ok(field_ids)
     }
 
     /// Creates an instance following the shape of the schema with the respective schema_id
+    ///
+    /// `schema_id` can also be the name of a schema in the `SchemaRegistry`.
+    /// Returns the handle of the new document, which is passed to `update_instance` and `delete_instance`
     pub async fn create_instance(
         &self,
         schema_id: &str,
-        fields: &mut [StringTuple],
-    ) -> Result<String, String> {
+        fields: &mut [FieldTuple],
+    ) -> Result<DocumentHandle> {
         sort_fields(fields);
-        let payload_content: Vec<String> = fields_to_json_fields(fields);
+        let schema_id: SchemaId = self.resolve_schema_id(schema_id).parse()?;
+        self.validate_fields(&schema_id, fields, true).await?;
+        let operation = operations::create(&schema_id, fields)?;
+        let id = self.send_to_node(&operation).await?;
 
-        // [1, 0, "chat_0020cae3b...", {"msg": "...", "username": "..." } ]
+        Ok(DocumentHandle::created(schema_id, id))
+    }
 
-        let json = format!(
-            r#"[{}, {}, "{}", {{ {} }} ]"#,
-            self.version,
-            OperationAction::Create,
-            schema_id,
-            payload_content.join(", ")
-        );
+    /// Creates many instances of the schema with the respective schema_id, see `publish_batch`
+    ///
+    /// Returns the result of every instance in the same order
+    pub async fn create_instances(
+        &self,
+        schema_id: &str,
+        instances: Vec<Vec<FieldTuple>>,
+    ) -> Vec<Result<DocumentHandle>> {
+        let schema_id = self.resolve_schema_id(schema_id);
+
+        let mut results: Vec<Option<Result<DocumentHandle>>> = Vec::with_capacity(instances.len());
+        let mut operations = Vec::with_capacity(instances.len());
+        let mut indices = Vec::with_capacity(instances.len());
+
+        for (i, mut fields) in instances.into_iter().enumerate() {
+            sort_fields(&mut fields);
+            let operation = match self.validate_fields(&schema_id, &fields, true).await {
+                Ok(()) => operations::create(&schema_id, &fields),
+                Err(err) => Err(err),
+            };
+
+            match operation {
+                Ok(operation) => {
+                    operations.push(operation);
+                    indices.push(i);
+                    results.push(None);
+                }
+                Err(err) => results.push(Some(Err(err))),
+            }
+        }
+
+        for (i, result) in indices
+            .into_iter()
+            .zip(self.publish_batch(operations).await)
+        {
+            results[i] =
+                Some(result.and_then(|id| Ok(DocumentHandle::created(schema_id.parse()?, id))));
+        }
+
+        // Every instance either failed to build or was published
+        results.into_iter().flatten().collect()
+    }
+
+    /// Publishes many operations, returning the operation id or error of every operation in the same order
+    ///
+    /// Operations in the same log (UPDATE and DELETE operations based on the same document) are
+    /// published one after the other, distinct logs are published concurrently, up to
+    /// `OperatorBuilder::batch_concurrency` at once
+    pub async fn publish_batch(&self, operations: Vec<Operation>) -> Vec<Result<OperationId>> {
+        let public_key = self.public_key().to_string();
+
+        // Group the operations by log, CREATE operations always open a new log
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut logs: HashMap<String, usize> = HashMap::new();
+
+        for (i, operation) in operations.iter().enumerate() {
+            let key = match operation.previous() {
+                Some(view_id) => self
+                    .logs()
+                    .log_id(&public_key, view_id)
+                    .map_or_else(|| view_id.to_string(), |log_id| log_id.as_u64().to_string()),
+                None => {
+                    groups.push(vec![i]);
+                    continue;
+                }
+            };
+
+            match logs.get(&key) {
+                Some(&group) => groups[group].push(i),
+                None => {
+                    logs.insert(key, groups.len());
+                    groups.push(vec![i]);
+                }
+            }
+        }
+
+        let mut results: Vec<Option<Result<OperationId>>> =
+            operations.iter().map(|_| None).collect();
 
-        self.send_to_node(&json).await
+        // New log ids are handed out locally once the next free one is known,
+        // until then the first CREATE operation is published alone
+        if !self.logs().knows_next_log_id(&public_key) {
+            let create = groups
+                .iter()
+                .position(|group| operations[group[0]].previous().is_none());
+
+            if let Some(position) = create {
+                for i in groups.remove(position) {
+                    results[i] = Some(self.send_to_node(&operations[i]).await);
+                }
+            }
+        }
+
+        let operations = &operations;
+        let published: Vec<Vec<(usize, Result<OperationId>)>> = stream::iter(groups)
+            .map(|group| async move {
+                let mut published = Vec::with_capacity(group.len());
+                for i in group {
+                    published.push((i, self.send_to_node(&operations[i]).await));
+                }
+                published
+            })
+            .buffer_unordered(self.batch_concurrency)
+            .collect()
+            .await;
+
+        for (i, result) in published.into_iter().flatten() {
+            results[i] = Some(result);
+        }
+
+        // Every operation was published
+        results.into_iter().flatten().collect()
     }
 
-    /// Updates partially or completely an instance with the respective view_id
+    /// Updates partially or completely the document at the view of the handle
+    ///
+    /// Returns the handle of the updated document, with the new view id
     pub async fn update_instance(
         &self,
-        schema_id: &str,
-        view_id: &str,
-        fields: &mut [StringTuple],
-    ) -> Result<String, String> {
+        document: &DocumentHandle,
+        fields: &mut [FieldTuple],
+    ) -> Result<DocumentHandle> {
         sort_fields(fields);
-        let to_update: Vec<String> = fields_to_json_fields(fields);
+        let schema_id = self.resolve_schema_id(&document.schema_id);
+        self.validate_fields(&schema_id, fields, false).await?;
+        let operation = operations::update(&schema_id, document.view_id.as_p2panda(), fields)?;
+        let id = self.send_to_node(&operation).await?;
 
-        //[1, 1, "chat_0020cae3b...", [ "<view_id>" ], { "username": "..." }]
+        Ok(document.at(id))
+    }
 
-        let json = format!(
-            r#"[{}, {}, "{}", [ "{}" ], {{ {} }} ]"#,
-            self.version,
-            OperationAction::Update,
-            schema_id,
-            view_id,
-            to_update.join(", ")
-        );
+    /// Deletes the document at the view of the handle, returning the handle of the deleted view
+    pub async fn delete_instance(&self, document: &DocumentHandle) -> Result<DocumentHandle> {
+        let schema_id = self.resolve_schema_id(&document.schema_id);
+        let operation = operations::delete(&schema_id, document.view_id.as_p2panda())?;
+        let id = self.send_to_node(&operation).await?;
 
-        self.send_to_node(&json).await
+        Ok(document.at(id))
     }
 
-    /// Deletes an instance with the respective view_id
-    pub async fn delete_instance(&self, schema_id: &str, view_id: &str) -> Result<String, String> {
-        let json = format!(
-            r#"[ {},{},"{}",["{}"] ]"#,
-            self.version,
-            OperationAction::Delete,
-            schema_id,
-            view_id
-        );
+    /// Creates the schema of `T`, returning its schema id
+    ///
+    /// `relations` maps the schema names of relation targets to their schema ids,
+    /// targets missing in `relations` are looked up in the `SchemaRegistry`
+    pub async fn create_typed_schema<T: Schema>(
+        &self,
+        relations: &[(&str, &str)],
+    ) -> Result<SchemaId> {
+        // relations which are not given are looked up in the registry
+        let given = typed::resolve_from(relations);
+        let resolve = |name: &str| {
+            given(name).or_else(|err| self.registry().schema_id(name).map(String::from).ok_or(err))
+        };
 
-        self.send_to_node(&json).await
+        let mut fields = T::field_defs(&resolve)?;
+        self.create_schema(T::NAME, T::DESCRIPTION, &mut fields)
+            .await
     }
 
-    /// Handles p2panda operations and graphql requests
-    async fn send_to_node(&self, json: &str) -> Result<String, String> {
-        // 1. Load public key from key_pair
-        let public_key = self.key_pair.public_key();
+    /// Creates an instance of the schema with the respective schema_id from `value`
+    pub async fn create_typed<T: Schema>(
+        &self,
+        schema_id: &str,
+        value: &T,
+    ) -> Result<DocumentHandle> {
+        self.create_instance(schema_id, &mut value.fields()).await
+    }
 
-        // 2. Parse operation from JSON string
-        let operation_result = serde_json::from_str(json);
+    /// Updates every field of the document with the fields of `value`
+    pub async fn update_typed<T: Schema>(
+        &self,
+        document: &DocumentHandle,
+        value: &T,
+    ) -> Result<DocumentHandle> {
+        self.update_instance(document, &mut value.fields()).await
+    }
 
-        let operation: PlainOperation = match operation_result {
-            Ok(op) => op,
-            Err(err) => return Err(err.to_string()),
-        };
+    /// Handles p2panda operations and graphql requests, retrying according to the `RetryPolicy`
+    async fn send_to_node(&self, operation: &Operation) -> Result<OperationId> {
+        let mut attempt = 1;
 
-        // 3. Send `nextArgs` GraphQL query to get the arguments from the node to create the next entry
-        let query = format!(
-            r#"
-            {{
-                nextArgs(publicKey: "{}", viewId: {}) {{
-                    logId
-                    seqNum
-                    skiplink
-                    backlink
-                }}
-            }}
-            "#,
-            public_key,
-            // Set `viewId` when `previous` is given in operation
-            operation
-                .previous()
-                .map_or("null".to_owned(), |id| format!("\"{}\"", id)),
-        );
+        loop {
+            match self.try_send_to_node(operation).await {
+                // A failed publish forgets the logs of the author,
+                // so the next attempt fetches `nextArgs` and signs again
+                Err(err) if self.retry.should_retry(attempt, &err) => {
+                    tokio::time::sleep(self.retry.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
 
-        let response_result = self.client.query_unwrap::<NextArgsResponse>(&query).await;
+    /// Publishes the operation once
+    ///
+    /// The arguments for the next entry are taken from the `LogCache` when possible,
+    /// `nextArgs` is only queried for unknown logs or when the node rejects the cached ones
+    async fn try_send_to_node(&self, operation: &Operation) -> Result<OperationId> {
+        // 1. Load public key from the signer and encode the operation
+        let public_key = self.public_key();
+        let encoded_operation = encode_operation(operation)?;
 
-        let response = match response_result {
-            Ok(res) => res,
-            Err(err) => {
-                return Err(format!(
-                    "GraphQL query to fetch `nextArgs` failed:\n{}",
-                    err
-                ))
+        // Offline, the entry is signed and queued in the outbox
+        if let Some(outbox) = &self.outbox {
+            return self.enqueue(outbox, operation, &encoded_operation).await;
+        }
+
+        let queue = self.queue.author(&public_key.to_string());
+
+        // 2. Try the locally tracked arguments first, only entries of the same log wait for each other
+        {
+            let _shared = queue.shared().await;
+            let lane = match operation.previous() {
+                Some(view_id) => self
+                    .logs()
+                    .log_id(&public_key.to_string(), view_id)
+                    .map(|log_id| queue.log(Some(log_id))),
+                None => Some(queue.log(None)),
+            };
+
+            if let Some(lane) = lane {
+                let _lane = lane.lock().await;
+
+                // Read after the entries published before in the same lane
+                let cached = self
+                    .logs()
+                    .next_args(&public_key.to_string(), operation.previous());
+
+                if let Some(args) = cached {
+                    match self
+                        .publish_entry(operation, &encoded_operation, args)
+                        .await
+                    {
+                        // The node has other arguments for this log, fall back to `nextArgs`
+                        Err(err) if err.is_log_conflict() => {}
+                        result => return result,
+                    }
+                }
             }
-        };
+        }
 
-        let NextArguments {
-            log_id,
-            seq_num,
-            skiplink,
-            backlink,
-        } = response.next_args;
-
-        // 4. Create p2panda data! Encode operation, sign and encode entry
-        let encoded_operation_result = encode_plain_operation(&operation);
-        let encoded_operation = match encoded_operation_result {
-            Ok(enc) => enc,
-            Err(_err) => return Err("Could not encode operation".to_string()),
-        };
+        // 3. Ask the node for the arguments to create the next entry,
+        // based on `previous` when it's given in the operation.
+        // Nothing else of the author is published meanwhile, so the arguments stay valid
+        let _exclusive = queue.exclusive().await;
+        let args = self
+            .client
+            .next_args(&public_key, operation.previous())
+            .await?;
 
-        let encoded_entry_result = sign_and_encode_entry(
-            &log_id,
-            &seq_num,
-            skiplink.as_ref(),
-            backlink.as_ref(),
-            &encoded_operation,
-            &self.key_pair,
-        );
+        self.publish_entry(operation, &encoded_operation, args)
+            .await
+    }
 
-        let encoded_entry = match encoded_entry_result {
-            Ok(enc) => enc,
-            Err(_err) => return Err("Could not sign and encode entry".to_string()),
-        };
+    /// Signs the entry of the operation with the given arguments and publishes it,
+    /// keeping the `LogCache` up to date with the response
+    async fn publish_entry(
+        &self,
+        operation: &Operation,
+        encoded_operation: &EncodedOperation,
+        args: NextArguments,
+    ) -> Result<OperationId> {
+        let public_key = self.public_key().to_string();
+        let encoded_entry = sign_entry(self.signer.as_ref(), &args, encoded_operation).await?;
 
         let operation_id = encoded_entry.hash();
-        let query = format!(
-            r#"
-            mutation Publish {{
-                publish(entry: "{}", operation: "{}") {{
-                    logId
-                    seqNum
-                    skiplink
-                    backlink
-                }}
-            }}
-        "#,
-            encoded_entry, encoded_operation
-        );
 
-        let response_result = self.client.query_unwrap::<PublishResponse>(&query).await;
-        if let Err(err) = response_result {
-            return Err(format!("GraphQL mutation `publish` failed:\n{}", err));
+        match self.client.publish(&encoded_entry, encoded_operation).await {
+            Ok(next) => {
+                self.logs().published(
+                    &public_key,
+                    operation.previous(),
+                    &operation_id,
+                    &args,
+                    next,
+                );
+                Ok(OperationId::from(p2panda_rs::operation::OperationId::from(
+                    operation_id,
+                )))
+            }
+            Err(err) => {
+                self.logs().forget(&public_key);
+                Err(err)
+            }
         }
+    }
 
-        Ok(operation_id.to_string())
+    fn logs(&self) -> MutexGuard<'_, LogCache> {
+        // The cache is only a shortcut, a poisoned lock is still usable
+        self.logs.lock().unwrap_or_else(|err| err.into_inner())
     }
 
     pub fn debug_print_public_key(&self) {
-        let public_key = self.key_pair.public_key();
+        let public_key = self.public_key();
         println!("▶️ DEBUG PUB_KEY: {}", public_key);
     }
 
-    /// Fetches all the schema definitions returning `AllSchemaDefinitionResponse` or `String` on error
-    pub async fn get_all_schema_definition(&self) -> Result<AllSchemaDefinitionResponse, String> {
+    /// Fetches all the schema definitions returning `AllSchemaDefinitionResponse`
+    pub async fn get_all_schema_definition(&self) -> Result<AllSchemaDefinitionResponse> {
         let query = graphql::queries::get_all_schemas_query;
-        let data: AllSchemaDefinitionResponse = match self.client.query_unwrap(query).await {
-            Ok(data) => data,
-            Err(err) => return Err(err.to_string()),
-        };
-
-        Ok(data)
+        self.query(query, None).await
     }
 
-    /// Fetches an specific schema definition returning `SchemaDefinitionResponse` or `String` on error
+    /// Fetches an specific schema definition returning `SchemaDefinitionResponse`
+    ///
+    /// Returns `Error::SchemaNotFound` if the node doesn't know the schema
     pub async fn get_schema_definition(
         &self,
         document_id: &str,
         view_id: &str,
-    ) -> Result<SchemaDefinitionResponse, String> {
+    ) -> Result<SchemaDefinitionResponse> {
         let query = graphql::queries::get_schema_query;
         let vars = GetSchemaVars {
             id: document_id.to_string(),
             view_id: view_id.to_string(),
         };
 
-        let data = match self.client.query_with_vars_unwrap(query, vars).await {
-            Ok(data) => data,
-            Err(err) => return Err(err.to_string()),
+        let data: MaybeSchemaDefinitionResponse =
+            self.query(query, Some(serde_json::to_value(vars)?)).await?;
+
+        match data.schema {
+            Some(schema) => Ok(SchemaDefinitionResponse { schema }),
+            None => Err(Error::SchemaNotFound(view_id.to_string())),
+        }
+    }
+
+    /// Fetches the schema definition with the respective `view_id`
+    ///
+    /// The schema definition is looked up using the first operation id of the view id
+    /// as document id, which is the case for every schema created with `create_schema`.
+    /// Updated schemas are searched in the latest schema definitions of the node
+    pub async fn find_schema_definition(&self, view_id: &str) -> Result<SchemaDefinition> {
+        let document_id = operations::view_id_from_str(view_id)?.graph_tips()[0].to_string();
+
+        match self.get_schema_definition(&document_id, view_id).await {
+            Ok(data) => Ok(data.schema),
+            Err(Error::SchemaNotFound(_)) => self
+                .get_all_schema_definition()
+                .await?
+                .all_schemas
+                .into_iter()
+                .find(|schema| schema.meta.view_id == view_id)
+                .ok_or_else(|| Error::SchemaNotFound(view_id.to_string())),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Fetches the field definitions of the schema with the respective `schema_id`
+    pub async fn get_schema_fields(&self, schema_id: &str) -> Result<Vec<FieldDefinition>> {
+        let schema_id = &self.resolve_schema_id(schema_id);
+        let view_id = match schema_id.parse::<SchemaId>()?.view_id() {
+            Some(view_id) => view_id,
+            None => return Err(Error::SchemaNotFound(schema_id.to_string())),
         };
 
-        Ok(data)
+        let schema = self
+            .find_schema_definition(&view_id.to_string())
+            .await
+            .map_err(|err| match err {
+                Error::SchemaNotFound(_) => Error::SchemaNotFound(schema_id.to_string()),
+                err => err,
+            })?;
+
+        Ok(schema.fields.fields.into_iter().map(|f| f.fields).collect())
     }
 
-    pub async fn print_all_schemas(&self) -> Result<(), String> {
+    /// Fetches the document with the respective `document_id` of the schema with the respective `schema_id`
+    ///
+    /// The fields are returned as `FieldMap` or deserialized into any struct:
+    /// ```ignore
+    /// let doc: Document = op.get_document(&schema_id, &document_id).await?;
+    /// let doc: Document<Pokemon> = op.get_document(&schema_id, &document_id).await?;
+    /// ```
+    pub async fn get_document<T: DeserializeOwned>(
+        &self,
+        schema_id: &str,
+        document_id: &str,
+    ) -> Result<Document<T>> {
+        let schema_id = &self.resolve_schema_id(schema_id);
+        let definitions = self.get_schema_fields(schema_id).await?;
+        let query = graphql::queries::get_document_query(schema_id, &definitions);
+        let vars = GetDocumentVars {
+            id: document_id.to_string(),
+        };
+
+        let data: DocumentResponse = self
+            .query(&query, Some(serde_json::to_value(vars)?))
+            .await?;
+
+        match data.document {
+            Some(document) => into_typed_document(document, &definitions),
+            None => Err(Error::DocumentNotFound(document_id.to_string())),
+        }
+    }
+
+    /// Fetches all the documents of the schema with the respective `schema_id`
+    pub async fn get_all_documents<T: DeserializeOwned>(
+        &self,
+        schema_id: &str,
+    ) -> Result<Vec<Document<T>>> {
+        let schema_id = &self.resolve_schema_id(schema_id);
+        let definitions = self.get_schema_fields(schema_id).await?;
+        let query = graphql::queries::get_all_documents_query(schema_id, &definitions);
+
+        let data: AllDocumentsResponse = self.query(&query, None).await?;
+
+        data.documents
+            .into_iter()
+            .map(|document| into_typed_document(document, &definitions))
+            .collect()
+    }
+
+    /// Runs a GraphQL query on the node and deserializes the `data` of the response
+    pub async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let data = self.client.query(query, variables).await?;
+        Ok(serde_json::from_value(data)?)
+    }
+
+    pub async fn print_all_schemas(&self) -> Result<()> {
         let data = self.get_all_schema_definition().await?;
-        serde_json::to_string_pretty(&data).map_err(|err| err.to_string())?;
+        serde_json::to_string_pretty(&data)?;
         Ok(())
     }
 
-    pub async fn print_schema(&self, document_id: &str, view_id: &str) -> Result<(), String> {
+    pub async fn print_schema(&self, document_id: &str, view_id: &str) -> Result<()> {
         let data = self.get_schema_definition(document_id, view_id).await?;
-        serde_json::to_string_pretty(&data).map_err(|err| err.to_string())?;
+        serde_json::to_string_pretty(&data)?;
         Ok(())
     }
 }
+
+/// Replaces the related documents with their ids and deserializes the fields into `T`
+pub(crate) fn into_typed_document<T: DeserializeOwned>(
+    document: Document,
+    definitions: &[FieldDefinition],
+) -> Result<Document<T>> {
+    let Document { meta, mut fields } = document;
+    flatten_relations(&mut fields, definitions);
+
+    Ok(Document {
+        meta,
+        fields: serde_json::from_value(serde_json::Value::Object(fields))?,
+    })
+}
+
+/// Checks if the schema has the same name, description and sorted `fields`
+pub(crate) fn schema_matches(
+    schema: &SchemaDefinition,
+    name: &str,
+    description: &str,
+    fields: &[StringTuple],
+) -> bool {
+    let definition = &schema.fields;
+    if definition.name != name || definition.description != description {
+        return false;
+    }
+
+    let mut existing: Vec<(&str, &str)> = definition
+        .fields
+        .iter()
+        .map(|f| (f.fields.name.as_str(), f.fields.type_.as_str()))
+        .collect();
+    existing.sort();
+
+    existing.len() == fields.len()
+        && existing
+            .iter()
+            .zip(fields)
+            .all(|((name, type_), (n, t))| name == n && type_ == t)
+}
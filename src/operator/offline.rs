@@ -0,0 +1,138 @@
+use super::Operator;
+use crate::ids::{DocumentViewId, OperationId};
+use crate::outbox::{Outbox, OutboxItem, OutboxStatus};
+use crate::signer::sign_entry;
+use crate::{Error, Result};
+
+use p2panda_rs::identity::PublicKey;
+use p2panda_rs::operation::{traits::Actionable, EncodedOperation, Operation};
+use std::collections::HashSet;
+use tokio::sync::Mutex as AsyncMutex;
+
+impl Operator {
+    /// Signs the entry of the operation against the log state of the outbox and queues it
+    pub(super) async fn enqueue(
+        &self,
+        outbox: &AsyncMutex<Outbox>,
+        operation: &Operation,
+        encoded_operation: &EncodedOperation,
+    ) -> Result<OperationId> {
+        let public_key = self.public_key();
+        let author = public_key.to_string();
+
+        let mut fetched = None;
+        let (mut outbox, args) = loop {
+            let mut guard = outbox.lock().await;
+
+            // Another entry may have been queued in the log meanwhile, the state of the outbox wins
+            if let Some(args) = guard.next_args(&author, operation.previous())? {
+                break (guard, args);
+            }
+            if let Some(args) = fetched.take() {
+                guard.seed(&author, operation.previous(), &args);
+                break (guard, args);
+            }
+            drop(guard);
+
+            // The log is written to the first time, its state is fetched from the node once.
+            // The outbox isn't locked meanwhile, so a slow node doesn't hold back other entries
+            fetched = Some(
+                self.client
+                    .next_args(&public_key, operation.previous())
+                    .await?,
+            );
+        };
+
+        let entry = sign_entry(self.signer.as_ref(), &args, encoded_operation).await?;
+        outbox.push(
+            &author,
+            operation.previous(),
+            &args,
+            &entry,
+            encoded_operation,
+        )
+    }
+
+    /// Publishes the entries of the outbox in the order they were signed
+    ///
+    /// Returns every item with its new status. Published items are removed from the outbox,
+    /// if the node can't be reached the remaining items stay pending for the next flush.
+    /// Entries following a rejected entry in the same log stay pending as well, see `discard_failed`.
+    /// An entry the node holds already, e.g. after a flush whose response got lost, counts as published
+    pub async fn flush(&self) -> Result<Vec<OutboxItem>> {
+        let mut outbox = self.outbox()?.lock().await;
+        let mut items = outbox.items().to_vec();
+
+        let mut blocked: HashSet<(String, u64)> = HashSet::new();
+
+        for item in items.iter_mut() {
+            let log = (item.public_key.clone(), item.log_id);
+            if blocked.contains(&log) {
+                continue;
+            }
+
+            item.status = match self.publish_item(item).await {
+                Ok(()) => OutboxStatus::Published,
+                // Nothing can be published until the node is reachable again
+                Err(err) if err.is_transport() => break,
+                Err(err) => {
+                    blocked.insert(log);
+                    OutboxStatus::Failed(err.to_string())
+                }
+            };
+        }
+
+        // The statuses are saved once, so a failed save doesn't lose them halfway
+        outbox.apply_flush(&items)?;
+        Ok(items)
+    }
+
+    /// Removes the rejected entries from the outbox, together with the entries following them
+    /// in the same log, and returns them
+    ///
+    /// The outbox forgets the state of their logs, the next entry in such a log asks the node
+    /// for `nextArgs`. Pending entries in logs created after a removed new log are rejected by the node
+    pub async fn discard_failed(&self) -> Result<Vec<OutboxItem>> {
+        self.outbox()?.lock().await.discard_failed()
+    }
+
+    /// Publishes the entry of an outbox item
+    async fn publish_item(&self, item: &OutboxItem) -> Result<()> {
+        let (entry, operation) = item.decode()?;
+
+        match self.client.publish(&entry, &operation).await {
+            Ok(_) => Ok(()),
+            Err(err) if err.is_log_conflict() && self.is_published(item).await => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns `true` if the node holds the entry of the item, its log continues after it
+    async fn is_published(&self, item: &OutboxItem) -> bool {
+        let public_key = match PublicKey::new(&item.public_key) {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        };
+        let view_id = DocumentViewId::from(item.operation_id.clone());
+
+        match self
+            .client
+            .next_args(&public_key, Some(view_id.as_p2panda()))
+            .await
+        {
+            Ok(args) => args.log_id.as_u64() == item.log_id && args.seq_num.as_u64() > item.seq_num,
+            Err(_) => false,
+        }
+    }
+
+    /// Entries waiting in the outbox, in the order they were signed
+    pub async fn outbox_items(&self) -> Result<Vec<OutboxItem>> {
+        Ok(self.outbox()?.lock().await.items().to_vec())
+    }
+
+    fn outbox(&self) -> Result<&AsyncMutex<Outbox>> {
+        self.outbox
+            .as_deref()
+            .ok_or_else(|| Error::Outbox("the Operator has no outbox".to_string()))
+    }
+}
@@ -0,0 +1,193 @@
+use crate::builder::fields::FieldValue;
+use crate::operator::{FieldTuple, StringTuple};
+use crate::{Error, Result};
+
+use p2panda_rs::document::{DocumentId, DocumentViewId};
+use p2panda_rs::operation::{
+    Operation, OperationAction, OperationBuilder, OperationValue, PinnedRelation,
+    PinnedRelationList, Relation, RelationList,
+};
+use p2panda_rs::schema::SchemaId;
+use std::str::FromStr;
+
+// Helpers to build p2panda operations, every value is passed to p2panda as is
+// so strings containing quotes, backslashes or newlines can't break the operation
+// ------------------------------------------------
+
+/// Builds the CREATE operation of a `schema_definition_v1` document
+///
+/// `field_ids` are the operation ids of the published `schema_field_definition_v1` documents
+pub fn schema_definition(name: &str, description: &str, field_ids: &[String]) -> Result<Operation> {
+    let fields = pinned_relation_list(
+        "fields",
+        &field_ids
+            .iter()
+            .map(|id| vec![id.clone()])
+            .collect::<Vec<_>>(),
+    )?;
+
+    let operation = OperationBuilder::new(&SchemaId::SchemaDefinition(1))
+        .fields(&[
+            ("name", name.into()),
+            ("description", description.into()),
+            ("fields", fields),
+        ])
+        .build()?;
+
+    Ok(operation)
+}
+
+/// Builds the UPDATE operation of the `schema_definition_v1` document view with the respective `view_id`
+pub fn schema_definition_update(
+    view_id: &str,
+    description: &str,
+    field_ids: &[String],
+) -> Result<Operation> {
+    let fields = pinned_relation_list(
+        "fields",
+        &field_ids
+            .iter()
+            .map(|id| vec![id.clone()])
+            .collect::<Vec<_>>(),
+    )?;
+
+    let operation = OperationBuilder::new(&SchemaId::SchemaDefinition(1))
+        .action(OperationAction::Update)
+        .previous(&view_id_from_str(view_id)?)
+        .fields(&[("description", description.into()), ("fields", fields)])
+        .build()?;
+
+    Ok(operation)
+}
+
+/// Builds the CREATE operation of a `schema_field_definition_v1` document
+pub fn schema_field_definition((name, field_type): &StringTuple) -> Result<Operation> {
+    let operation = OperationBuilder::new(&SchemaId::SchemaFieldDefinition(1))
+        .fields(&[
+            ("name", name.as_str().into()),
+            ("type", field_type.as_str().into()),
+        ])
+        .build()?;
+
+    Ok(operation)
+}
+
+/// Builds the CREATE operation of a document of the schema with the respective `schema_id`
+pub fn create(schema_id: &str, fields: &[FieldTuple]) -> Result<Operation> {
+    let fields = operation_fields(fields)?;
+
+    let operation = OperationBuilder::new(&schema_id_from_str(schema_id)?)
+        .fields(&as_field_refs(&fields))
+        .build()?;
+
+    Ok(operation)
+}
+
+/// Builds the UPDATE operation of the document view with the respective `view_id`
+pub fn update(
+    schema_id: &str,
+    view_id: &DocumentViewId,
+    fields: &[FieldTuple],
+) -> Result<Operation> {
+    let fields = operation_fields(fields)?;
+
+    let operation = OperationBuilder::new(&schema_id_from_str(schema_id)?)
+        .action(OperationAction::Update)
+        .previous(view_id)
+        .fields(&as_field_refs(&fields))
+        .build()?;
+
+    Ok(operation)
+}
+
+/// Builds the DELETE operation of the document view with the respective `view_id`
+pub fn delete(schema_id: &str, view_id: &DocumentViewId) -> Result<Operation> {
+    let operation = OperationBuilder::new(&schema_id_from_str(schema_id)?)
+        .action(OperationAction::Delete)
+        .previous(view_id)
+        .build()?;
+
+    Ok(operation)
+}
+
+/// Converts every field value to an `OperationValue`, rejecting duplicated field names
+pub fn operation_fields(fields: &[FieldTuple]) -> Result<Vec<(String, OperationValue)>> {
+    let mut result: Vec<(String, OperationValue)> = Vec::with_capacity(fields.len());
+
+    for (name, value) in fields {
+        if result.iter().any(|(n, _)| n == name) {
+            return Err(Error::InvalidFieldValue {
+                name: name.clone(),
+                reason: "field is defined more than once".to_string(),
+            });
+        }
+
+        result.push((name.clone(), operation_value(name, value)?));
+    }
+
+    Ok(result)
+}
+
+/// Converts a `FieldValue` to an `OperationValue`, validating the relation ids
+pub fn operation_value(name: &str, value: &FieldValue) -> Result<OperationValue> {
+    let value = match value {
+        FieldValue::Bool(b) => OperationValue::Boolean(*b),
+        FieldValue::Int(x) => OperationValue::Integer(*x),
+        FieldValue::Float(x) if x.is_finite() => OperationValue::Float(*x),
+        FieldValue::Float(x) => return Err(invalid_value(name, format!("{} is not finite", x))),
+        FieldValue::Str(s) => OperationValue::String(s.clone()),
+        FieldValue::Relation(id) => OperationValue::Relation(Relation::new(document_id(name, id)?)),
+        FieldValue::RelationList(ids) => OperationValue::RelationList(RelationList::new(
+            ids.iter()
+                .map(|id| document_id(name, id))
+                .collect::<Result<_>>()?,
+        )),
+        FieldValue::PinnedRelation(ids) => {
+            OperationValue::PinnedRelation(PinnedRelation::new(view_id(name, ids)?))
+        }
+        FieldValue::PinnedRelationList(ids) => return pinned_relation_list(name, ids),
+    };
+
+    Ok(value)
+}
+
+fn pinned_relation_list(name: &str, ids: &[Vec<String>]) -> Result<OperationValue> {
+    Ok(OperationValue::PinnedRelationList(PinnedRelationList::new(
+        ids.iter()
+            .map(|ids| view_id(name, ids))
+            .collect::<Result<_>>()?,
+    )))
+}
+
+fn document_id(name: &str, id: &str) -> Result<DocumentId> {
+    DocumentId::from_str(id).map_err(|err| invalid_value(name, err.to_string()))
+}
+
+fn view_id(name: &str, ids: &[String]) -> Result<DocumentViewId> {
+    DocumentViewId::from_str(&ids.join("_")).map_err(|err| invalid_value(name, err.to_string()))
+}
+
+fn invalid_value(name: &str, reason: String) -> Error {
+    Error::InvalidFieldValue {
+        name: name.to_string(),
+        reason,
+    }
+}
+
+fn as_field_refs(fields: &[(String, OperationValue)]) -> Vec<(&str, OperationValue)> {
+    fields
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.clone()))
+        .collect()
+}
+
+pub fn schema_id_from_str(schema_id: &str) -> Result<SchemaId> {
+    crate::ids::SchemaId::from_str(schema_id).map(SchemaId::from)
+}
+
+pub fn view_id_from_str(view_id: &str) -> Result<DocumentViewId> {
+    DocumentViewId::from_str(view_id).map_err(|source| Error::InvalidViewId {
+        id: view_id.to_string(),
+        source,
+    })
+}
@@ -0,0 +1,48 @@
+use p2panda_rs::entry::LogId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, RwLock};
+
+/// Serialises the entries written to the same log, while distinct logs are published in parallel
+///
+/// Entries with locally known arguments hold the shared lane of their author and the lane of their log.
+/// New logs have to be opened in order, so the CREATE operations of an author share a lane as well.
+/// Entries whose log is unknown ask the node for `nextArgs`, which is only consistent
+/// while nothing else of the author is in flight, so they hold the exclusive lane
+#[derive(Debug, Default)]
+pub(crate) struct PublishQueue {
+    authors: Mutex<HashMap<String, Arc<AuthorQueue>>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AuthorQueue {
+    lane: RwLock<()>,
+    logs: Mutex<HashMap<Option<LogId>, Arc<AsyncMutex<()>>>>,
+}
+
+impl PublishQueue {
+    /// Queue of the author with the respective public key
+    pub fn author(&self, public_key: &str) -> Arc<AuthorQueue> {
+        // Only holds handles of the locks, a poisoned lock is still usable
+        let mut authors = self.authors.lock().unwrap_or_else(|err| err.into_inner());
+        authors.entry(public_key.to_string()).or_default().clone()
+    }
+}
+
+impl AuthorQueue {
+    /// Shared lane of the author, held while publishing to a known log
+    pub async fn shared(&self) -> tokio::sync::RwLockReadGuard<'_, ()> {
+        self.lane.read().await
+    }
+
+    /// Exclusive lane of the author, held while publishing with arguments of the node
+    pub async fn exclusive(&self) -> tokio::sync::RwLockWriteGuard<'_, ()> {
+        self.lane.write().await
+    }
+
+    /// Lane of the log, held while publishing to it. `None` is the lane of new logs
+    pub fn log(&self, log_id: Option<LogId>) -> Arc<AsyncMutex<()>> {
+        let mut logs = self.logs.lock().unwrap_or_else(|err| err.into_inner());
+        logs.entry(log_id).or_default().clone()
+    }
+}
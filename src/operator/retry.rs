@@ -0,0 +1,103 @@
+use crate::Error;
+
+use rand::Rng;
+use std::time::Duration;
+
+/// When and how often `Operator` retries publishing an operation
+///
+/// Operations are published once by default. With more attempts, transport errors and
+/// `Error::LogConflict` (e.g. a seq num another publish took meanwhile) are retried,
+/// every other error is returned right away, e.g. invalid fields or operations the node rejects.
+/// Every retry fetches `nextArgs` from the node and signs the entry again, so an operation whose
+/// publish response got lost on the way back may be published twice.
+/// The time between attempts doubles, starting at `initial_backoff` up to `max_backoff`
+///
+/// ```
+/// # use std::time::Duration;
+/// # use zenode::RetryPolicy;
+/// let policy = RetryPolicy::default()
+///     .max_attempts(5)
+///     .initial_backoff(Duration::from_millis(200))
+///     .jitter(0.5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    /// A single attempt, with more attempts 100ms to 5s between them with up to 20% jitter
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Publishes every operation once, errors are returned right away. Same as `default()`
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Number of attempts including the first one, at least 1
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Time before the first retry
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Longest time between two attempts
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Fraction of the backoff which is randomised, between 0 and 1
+    ///
+    /// With `0.2` the time before a retry lies between 80% and 100% of the backoff,
+    /// so Operators failing at the same time don't retry at the same time. NaN disables the jitter
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = if jitter.is_nan() {
+            0.0
+        } else {
+            jitter.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    /// Returns `true` if `err` of attempt `attempt` (starting at 1) is worth another attempt
+    pub(crate) fn should_retry(&self, attempt: u32, err: &Error) -> bool {
+        attempt < self.max_attempts && (err.is_transport() || err.is_log_conflict())
+    }
+
+    /// Time before retrying after the failed attempt `attempt` (starting at 1), without jitter
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Time before retrying after the failed attempt `attempt`, with jitter
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        if self.jitter == 0.0 {
+            return backoff;
+        }
+
+        let random: f64 = rand::thread_rng().gen_range(0.0, self.jitter);
+        backoff.mul_f64(1.0 - random)
+    }
+}
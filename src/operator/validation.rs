@@ -0,0 +1,212 @@
+use super::{Operator, StringTuple};
+use crate::builder::fields::{FieldType, FieldValue};
+use crate::operator::operations;
+use crate::{Error, FieldTuple, Result};
+
+use p2panda_rs::document::{DocumentId, DocumentViewId};
+use p2panda_rs::schema::SchemaId;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Problem with a field found by the client side validation of `create_instance` and `update_instance`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldProblem {
+    /// The schema has no field with this name
+    Unknown(String),
+    /// The field of the schema is not given, only for CREATE operations
+    Missing(String),
+    /// The value doesn't match the type of the field
+    WrongType {
+        name: String,
+        expected: String,
+        found: String,
+    },
+    /// The value has the right type but is invalid, e.g. a malformed document id
+    InvalidValue { name: String, reason: String },
+}
+
+impl Display for FieldProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldProblem::Unknown(name) => write!(f, "unknown field '{}'", name),
+            FieldProblem::Missing(name) => write!(f, "missing field '{}'", name),
+            FieldProblem::WrongType {
+                name,
+                expected,
+                found,
+            } => write!(f, "field '{}' expects {} but got {}", name, expected, found),
+            FieldProblem::InvalidValue { name, reason } => {
+                write!(f, "invalid value for field '{}': {}", name, reason)
+            }
+        }
+    }
+}
+
+impl Operator {
+    /// Checks the fields against the definition of the schema before anything is signed
+    ///
+    /// `create` requires every field of the schema. Only application schemas are validated
+    pub(crate) async fn validate_fields(
+        &self,
+        schema_id: &str,
+        fields: &[FieldTuple],
+        create: bool,
+    ) -> Result<()> {
+        if !self.validate {
+            return Ok(());
+        }
+
+        match operations::schema_id_from_str(schema_id)? {
+            SchemaId::Application(..) => {}
+            _ => return Ok(()),
+        }
+
+        let definitions = match self.schema_field_types(schema_id).await? {
+            Some(definitions) => definitions,
+            None => return Ok(()),
+        };
+        let problems = field_problems(&definitions, fields, create);
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidFields {
+                schema_id: schema_id.to_string(),
+                problems,
+            })
+        }
+    }
+
+    /// Field names and types of the schema, fetched once per schema id
+    ///
+    /// Returns `None` if the schema is unknown to the outbox and the node can't be reached
+    async fn schema_field_types(&self, schema_id: &str) -> Result<Option<Vec<StringTuple>>> {
+        if let Some(fields) = self.definitions().get(schema_id) {
+            return Ok(Some(fields.clone()));
+        }
+
+        if let Some(outbox) = &self.outbox {
+            let fields = outbox
+                .lock()
+                .await
+                .schema_fields(schema_id)
+                .map(<[_]>::to_vec);
+            if let Some(fields) = fields {
+                self.definitions()
+                    .insert(schema_id.to_string(), fields.clone());
+                return Ok(Some(fields));
+            }
+        }
+
+        let fields: Vec<StringTuple> = match self.get_schema_fields(schema_id).await {
+            Ok(fields) => fields
+                .into_iter()
+                .map(|field| (field.name, field.type_))
+                .collect(),
+            // Offline, the node validates the fields once the outbox is flushed
+            Err(err) if err.is_transport() && self.outbox.is_some() => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        self.record_definitions(schema_id, &fields).await?;
+        Ok(Some(fields))
+    }
+}
+
+/// Every problem of `fields` compared to the field definitions of a schema
+pub(crate) fn field_problems(
+    definitions: &[StringTuple],
+    fields: &[FieldTuple],
+    create: bool,
+) -> Vec<FieldProblem> {
+    let mut problems = Vec::new();
+
+    for (i, (name, value)) in fields.iter().enumerate() {
+        // Duplicated fields are rejected with `Error::InvalidFieldValue` when building the operation
+        if fields[..i].iter().any(|(n, _)| n == name) {
+            continue;
+        }
+
+        match definitions.iter().find(|(n, _)| n == name) {
+            Some((_, field_type)) => problems.extend(value_problem(name, field_type, value)),
+            None => problems.push(FieldProblem::Unknown(name.clone())),
+        }
+    }
+
+    if create {
+        for (name, _) in definitions {
+            if !fields.iter().any(|(n, _)| n == name) {
+                problems.push(FieldProblem::Missing(name.clone()));
+            }
+        }
+    }
+
+    problems
+}
+
+fn value_problem(name: &str, field_type: &str, value: &FieldValue) -> Option<FieldProblem> {
+    use FieldValue::*;
+
+    let invalid = |reason: String| {
+        Some(FieldProblem::InvalidValue {
+            name: name.to_string(),
+            reason,
+        })
+    };
+
+    // Relations are encoded like strings and string arrays, so those values are accepted as well
+    match (FieldType::parse(field_type), value) {
+        (Some(FieldType::Bool), Bool(_))
+        | (Some(FieldType::Int), Int(_))
+        | (Some(FieldType::Str), Str(_)) => None,
+        (Some(FieldType::Float), Float(x)) if x.is_finite() => None,
+        (Some(FieldType::Float), Float(x)) => invalid(format!("{} is not finite", x)),
+        (Some(FieldType::Relation(_)), Relation(id) | Str(id)) => {
+            document_id_problem(id).and_then(invalid)
+        }
+        (Some(FieldType::RelationList(_)), RelationList(ids)) => ids
+            .iter()
+            .find_map(|id| document_id_problem(id))
+            .and_then(invalid),
+        (Some(FieldType::PinnedRelation(_)), PinnedRelation(ids) | RelationList(ids)) => {
+            view_id_problem(ids).and_then(invalid)
+        }
+        (Some(FieldType::PinnedRelationList(_)), PinnedRelationList(view_ids)) => view_ids
+            .iter()
+            .find_map(|ids| view_id_problem(ids))
+            .and_then(invalid),
+        // Unknown types are left to the node
+        (None, _) => None,
+        (Some(_), value) => Some(FieldProblem::WrongType {
+            name: name.to_string(),
+            expected: field_type.to_string(),
+            found: value_kind(value).to_string(),
+        }),
+    }
+}
+
+fn document_id_problem(id: &str) -> Option<String> {
+    DocumentId::from_str(id)
+        .err()
+        .map(|err| format!("'{}' is not a document id: {}", id, err))
+}
+
+fn view_id_problem(ids: &[String]) -> Option<String> {
+    let view_id = ids.join("_");
+    DocumentViewId::from_str(&view_id)
+        .err()
+        .map(|err| format!("'{}' is not a document view id: {}", view_id, err))
+}
+
+fn value_kind(value: &FieldValue) -> &'static str {
+    match value {
+        FieldValue::Bool(_) => "bool",
+        FieldValue::Int(_) => "int",
+        FieldValue::Float(_) => "float",
+        FieldValue::Str(_) => "str",
+        FieldValue::Relation(_) => "relation",
+        FieldValue::RelationList(_) => "relation_list",
+        FieldValue::PinnedRelation(_) => "pinned_relation",
+        FieldValue::PinnedRelationList(_) => "pinned_relation_list",
+    }
+}
@@ -0,0 +1,161 @@
+use super::{into_typed_document, Operator};
+use crate::graphql::queries::{get_all_documents_query, get_document_query};
+use crate::graphql::schemas::{AllDocumentsResponse, Document, DocumentResponse, FieldDefinition};
+use crate::{Error, Result};
+
+use futures::future::{self, Either};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+impl Operator {
+    /// Streams every new view of the document with the respective `document_id`,
+    /// starting with the current one
+    ///
+    /// Polls the node every `OperatorBuilder::watch_interval`, views are deduplicated by view id.
+    /// Failed polls are yielded as errors and retried with exponential backoff,
+    /// the stream ends with `Error::DocumentNotFound` once the document is deleted
+    pub fn watch_document<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        schema_id: &str,
+        document_id: &str,
+    ) -> impl Stream<Item = Result<Document<T>>> + 'a {
+        let document_id = document_id.to_string();
+
+        self.watch_definitions(schema_id).flat_map(move |result| {
+            let (schema_id, definitions) = match result {
+                Ok(schema) => schema,
+                Err(err) => return Either::Left(stream::once(future::ready(Err(err)))),
+            };
+
+            let query = get_document_query(&schema_id, &definitions);
+            let variables = json!({ "id": document_id });
+            let document_id = document_id.clone();
+
+            let views = self
+                .watch_data(query, Some(variables))
+                .scan((None::<String>, false), move |(last, deleted), data| {
+                    if *deleted {
+                        return future::ready(None);
+                    }
+
+                    let response =
+                        data.and_then(|data| Ok(serde_json::from_value::<DocumentResponse>(data)?));
+
+                    let item = match response {
+                        Ok(DocumentResponse {
+                            document: Some(document),
+                        }) => {
+                            if last.as_deref() == Some(document.meta.view_id.as_str()) {
+                                None
+                            } else {
+                                *last = Some(document.meta.view_id.clone());
+                                Some(into_typed_document(document, &definitions))
+                            }
+                        }
+                        Ok(DocumentResponse { document: None }) => {
+                            *deleted = true;
+                            Some(Err(Error::DocumentNotFound(document_id.clone())))
+                        }
+                        Err(err) => Some(Err(err)),
+                    };
+
+                    future::ready(Some(item))
+                })
+                .filter_map(future::ready);
+
+            Either::Right(views)
+        })
+    }
+
+    /// Streams the documents of the schema with the respective `schema_id`
+    /// every time one is created or updated, starting with the current ones
+    ///
+    /// Like `watch_document`, documents are deduplicated by view id
+    /// and failed polls are yielded as errors and retried with exponential backoff
+    pub fn watch_schema<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        schema_id: &str,
+    ) -> impl Stream<Item = Result<Document<T>>> + 'a {
+        self.watch_definitions(schema_id).flat_map(move |result| {
+            let (schema_id, definitions) = match result {
+                Ok(schema) => schema,
+                Err(err) => return Either::Left(stream::once(future::ready(Err(err)))),
+            };
+
+            let query = get_all_documents_query(&schema_id, &definitions);
+
+            let documents = self
+                .watch_data(query, None)
+                .scan(HashMap::<String, String>::new(), move |seen, data| {
+                    let response = data
+                        .and_then(|data| Ok(serde_json::from_value::<AllDocumentsResponse>(data)?));
+
+                    let mut changed = Vec::new();
+                    match response {
+                        Ok(response) => {
+                            for document in response.documents {
+                                let meta = &document.meta;
+                                if seen.get(&meta.document_id) == Some(&meta.view_id) {
+                                    continue;
+                                }
+
+                                seen.insert(meta.document_id.clone(), meta.view_id.clone());
+                                changed.push(into_typed_document(document, &definitions));
+                            }
+                        }
+                        Err(err) => changed.push(Err(err)),
+                    }
+
+                    future::ready(Some(stream::iter(changed)))
+                })
+                .flatten();
+
+            Either::Right(documents)
+        })
+    }
+
+    /// Fetches the field definitions of the schema once, before watching its documents
+    fn watch_definitions<'a>(
+        &'a self,
+        schema_id: &str,
+    ) -> impl Stream<Item = Result<(String, Vec<FieldDefinition>)>> + 'a {
+        let schema_id = self.resolve_schema_id(schema_id);
+
+        stream::once(async move {
+            let definitions = self.get_schema_fields(&schema_id).await?;
+            Ok((schema_id, definitions))
+        })
+    }
+
+    /// Streams the `data` of the query polled every `watch_interval`
+    fn watch_data<'a>(
+        &'a self,
+        query: String,
+        variables: Option<Value>,
+    ) -> BoxStream<'a, Result<Value>> {
+        let interval = self.watch_interval;
+        let max_backoff = self.watch_max_backoff;
+
+        stream::unfold(Duration::ZERO, move |delay| {
+            let query = query.clone();
+            let variables = variables.clone();
+
+            async move {
+                tokio::time::sleep(delay).await;
+                let result = self.client.query(&query, variables).await;
+
+                // Back off after failed polls, doubling the delay up to `max_backoff`
+                let next = match result {
+                    Ok(_) => interval,
+                    Err(_) => (delay.max(interval) * 2).min(max_backoff).max(interval),
+                };
+
+                Some((result, next))
+            }
+        })
+        .boxed()
+    }
+}
@@ -0,0 +1,361 @@
+//! Offline outbox, entries are signed locally and published later with `Operator::flush`
+//!
+//! Enabled with `OperatorBuilder::outbox`. The outbox file holds the queued entries and the
+//! log state of every author, so entries are signed without reaching the node. Only the first
+//! entry in a log the outbox doesn't know yet asks the node for `nextArgs`. The field definitions
+//! of the schemas used offline are kept as well, so fields are validated without the node
+use crate::graphql::schemas::NextArguments;
+use crate::ids::OperationId;
+use crate::operator::StringTuple;
+use crate::{Error, Result};
+
+use p2panda_rs::document::DocumentViewId;
+use p2panda_rs::entry::traits::AsEncodedEntry;
+use p2panda_rs::entry::{EncodedEntry, LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::operation::EncodedOperation;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State of an entry in the outbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Waiting for the next `Operator::flush`
+    Pending,
+    /// Accepted by the node, removed from the outbox after the flush
+    Published,
+    /// Rejected by the node, retried by the next flush unless removed with `Operator::discard_failed`.
+    /// Later entries of the same log wait for it
+    Failed(String),
+}
+
+/// Signed entry in the outbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutboxItem {
+    pub operation_id: OperationId,
+    pub public_key: String,
+    pub log_id: u64,
+    pub seq_num: u64,
+    /// Hex encoded entry
+    pub entry: String,
+    /// Hex encoded operation
+    pub operation: String,
+    pub status: OutboxStatus,
+}
+
+impl OutboxItem {
+    pub(crate) fn decode(&self) -> Result<(EncodedEntry, EncodedOperation)> {
+        let bytes = |hex_str: &str| {
+            hex::decode(hex_str).map_err(|err| {
+                Error::Outbox(format!("invalid entry {}: {}", self.operation_id, err))
+            })
+        };
+
+        Ok((
+            EncodedEntry::from_bytes(&bytes(&self.entry)?),
+            EncodedOperation::from_bytes(&bytes(&self.operation)?),
+        ))
+    }
+}
+
+/// Queued entries and log state, the content of the outbox file
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct OutboxState {
+    authors: BTreeMap<String, AuthorLogs>,
+    items: Vec<OutboxItem>,
+    /// Field definitions by schema id
+    #[serde(default)]
+    schemas: BTreeMap<String, Vec<StringTuple>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct AuthorLogs {
+    /// Next free log id, `None` until it's known from the node
+    next_log_id: Option<u64>,
+    logs: BTreeMap<u64, LogState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct LogState {
+    next_seq_num: u64,
+    /// Hashes of the entries by seq num as far as they are known, for the back- and skiplinks
+    hashes: BTreeMap<u64, String>,
+    /// Operations written to the log, to find the log of `previous`
+    operations: BTreeSet<String>,
+}
+
+/// File backed queue of signed entries, see `Operator::flush`
+#[derive(Debug)]
+pub struct Outbox {
+    path: PathBuf,
+    state: OutboxState,
+}
+
+impl Outbox {
+    /// Loads the outbox from a json file, the file is created on the first change if missing
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let state = if path.exists() {
+            let content = fs::read_to_string(&path).map_err(|source| Error::OutboxFile {
+                path: path.clone(),
+                source,
+            })?;
+            serde_json::from_str(&content)?
+        } else {
+            OutboxState::default()
+        };
+
+        Ok(Self { path, state })
+    }
+
+    /// Entries waiting in the outbox, in the order they were signed
+    pub fn items(&self) -> &[OutboxItem] {
+        &self.state.items
+    }
+
+    /// Field definitions of the schema with the respective `schema_id`, if they were recorded
+    pub(crate) fn schema_fields(&self, schema_id: &str) -> Option<&[StringTuple]> {
+        self.state.schemas.get(schema_id).map(Vec::as_slice)
+    }
+
+    /// Records the field definitions of a schema for the validation of later sessions
+    pub(crate) fn record_schema(&mut self, schema_id: &str, fields: &[StringTuple]) -> Result<()> {
+        if self.schema_fields(schema_id) == Some(fields) {
+            return Ok(());
+        }
+
+        self.state
+            .schemas
+            .insert(schema_id.to_string(), fields.to_vec());
+        self.save()
+    }
+
+    /// Returns the arguments for the next entry of an operation based on `previous`,
+    /// `None` if the log is unknown and its state has to be fetched from the node
+    pub(crate) fn next_args(
+        &self,
+        public_key: &str,
+        previous: Option<&DocumentViewId>,
+    ) -> Result<Option<NextArguments>> {
+        let author = match self.state.authors.get(public_key) {
+            Some(author) => author,
+            None => return Ok(None),
+        };
+
+        let (log_id, log) = match previous {
+            Some(view_id) => match find_log(author, view_id) {
+                Some(log) => log,
+                None => return Ok(None),
+            },
+            None => match author.next_log_id {
+                Some(log_id) => {
+                    return Ok(Some(NextArguments {
+                        log_id: LogId::new(log_id),
+                        seq_num: SeqNum::default(),
+                        skiplink: None,
+                        backlink: None,
+                    }))
+                }
+                None => return Ok(None),
+            },
+        };
+
+        let seq_num = SeqNum::new(log.next_seq_num)
+            .map_err(|err| Error::Outbox(format!("invalid seq num in log {}: {}", log_id, err)))?;
+
+        let hash = |link: Option<SeqNum>| -> Result<Option<Hash>> {
+            let link = match link.filter(|link| link.as_u64() > 0) {
+                Some(link) => link.as_u64(),
+                None => return Ok(None),
+            };
+
+            let hash = log.hashes.get(&link).ok_or_else(|| {
+                Error::Outbox(format!(
+                    "the hash of entry {} in log {} is unknown, flush while the node is reachable",
+                    link, log_id
+                ))
+            })?;
+            Hash::new(hash)
+                .map(Some)
+                .map_err(|err| Error::Outbox(err.to_string()))
+        };
+
+        let skiplink = seq_num
+            .skiplink_seq_num()
+            .filter(|skiplink| Some(*skiplink) != seq_num.backlink_seq_num());
+
+        Ok(Some(NextArguments {
+            log_id: LogId::new(log_id),
+            seq_num,
+            skiplink: hash(skiplink)?,
+            backlink: hash(seq_num.backlink_seq_num())?,
+        }))
+    }
+
+    /// Records the arguments the node returned for a log the outbox doesn't know yet
+    pub(crate) fn seed(
+        &mut self,
+        public_key: &str,
+        previous: Option<&DocumentViewId>,
+        args: &NextArguments,
+    ) {
+        let author = self
+            .state
+            .authors
+            .entry(public_key.to_string())
+            .or_default();
+
+        let log_id = args.log_id.as_u64();
+        if previous.is_none() {
+            author.next_log_id = Some(log_id);
+            return;
+        }
+
+        let log = author.logs.entry(log_id).or_default();
+        log.next_seq_num = args.seq_num.as_u64();
+
+        if let Some(backlink) = &args.backlink {
+            log.hashes
+                .insert(log.next_seq_num - 1, backlink.as_str().to_string());
+        }
+        if let (Some(skiplink), Some(seq_num)) = (&args.skiplink, args.seq_num.skiplink_seq_num()) {
+            log.hashes
+                .insert(seq_num.as_u64(), skiplink.as_str().to_string());
+        }
+
+        log.operations.extend(
+            previous
+                .into_iter()
+                .flat_map(|view_id| view_id.iter())
+                .map(|id| id.to_string()),
+        );
+    }
+
+    /// Queues an entry signed with `args` and advances its log, returning the operation id
+    pub(crate) fn push(
+        &mut self,
+        public_key: &str,
+        previous: Option<&DocumentViewId>,
+        args: &NextArguments,
+        entry: &EncodedEntry,
+        operation: &EncodedOperation,
+    ) -> Result<OperationId> {
+        let author = self
+            .state
+            .authors
+            .entry(public_key.to_string())
+            .or_default();
+
+        let log_id = args.log_id.as_u64();
+        let seq_num = args.seq_num.as_u64();
+        let hash = entry.hash().as_str().to_string();
+
+        // The node hands out new logs in order, so the next free log id follows the new one
+        if args.seq_num.is_first() {
+            author.next_log_id = Some(
+                author
+                    .next_log_id
+                    .map_or(log_id + 1, |id| id.max(log_id + 1)),
+            );
+        }
+
+        let log = author.logs.entry(log_id).or_default();
+        log.next_seq_num = seq_num + 1;
+        log.hashes.insert(seq_num, hash.clone());
+        log.operations.insert(hash.clone());
+        log.operations.extend(
+            previous
+                .into_iter()
+                .flat_map(|view_id| view_id.iter())
+                .map(|id| id.to_string()),
+        );
+
+        let operation_id: OperationId = hash.parse()?;
+        self.state.items.push(OutboxItem {
+            operation_id: operation_id.clone(),
+            public_key: public_key.to_string(),
+            log_id,
+            seq_num,
+            entry: entry.to_string(),
+            operation: operation.to_string(),
+            status: OutboxStatus::Pending,
+        });
+
+        self.save()?;
+        Ok(operation_id)
+    }
+
+    /// Replaces the items with the result of a flush, without the published ones
+    pub(crate) fn apply_flush(&mut self, items: &[OutboxItem]) -> Result<()> {
+        self.state.items = items
+            .iter()
+            .filter(|item| item.status != OutboxStatus::Published)
+            .cloned()
+            .collect();
+        self.save()
+    }
+
+    /// Removes the failed items and the items following them in the same log,
+    /// forgetting the state of those logs
+    pub(crate) fn discard_failed(&mut self) -> Result<Vec<OutboxItem>> {
+        let logs: BTreeSet<(String, u64)> = self
+            .state
+            .items
+            .iter()
+            .filter(|item| matches!(item.status, OutboxStatus::Failed(_)))
+            .map(|item| (item.public_key.clone(), item.log_id))
+            .collect();
+
+        if logs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (discarded, items): (Vec<_>, Vec<_>) = std::mem::take(&mut self.state.items)
+            .into_iter()
+            .partition(|item| logs.contains(&(item.public_key.clone(), item.log_id)));
+        self.state.items = items;
+
+        for item in &discarded {
+            let author = match self.state.authors.get_mut(&item.public_key) {
+                Some(author) => author,
+                None => continue,
+            };
+
+            author.logs.remove(&item.log_id);
+            // The node never saw the discarded new log, the next free log id is asked again
+            if item.seq_num == 1 {
+                author.next_log_id = None;
+            }
+        }
+
+        self.save()?;
+        Ok(discarded)
+    }
+
+    fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.state)?;
+
+        // Write to a temporary file first so a crash can't lose the queued entries
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|source| Error::OutboxFile {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+/// Log of the author the document with the respective view id is written to
+fn find_log<'a>(author: &'a AuthorLogs, view_id: &DocumentViewId) -> Option<(u64, &'a LogState)> {
+    author.logs.iter().find_map(|(log_id, log)| {
+        view_id
+            .iter()
+            .any(|id| log.operations.contains(id.as_str()))
+            .then_some((*log_id, log))
+    })
+}
@@ -0,0 +1,125 @@
+use crate::operator::StringTuple;
+use crate::{Error, Result};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Schema recorded in the `SchemaRegistry`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredSchema {
+    /// Schema id in the shape `<name>_<view_id>`
+    pub schema_id: String,
+    /// Starts at 1 and increases every time a new schema id is recorded for the same name
+    pub version: u64,
+    pub description: String,
+    /// Field names and types, sorted by name
+    pub fields: Vec<StringTuple>,
+}
+
+/// Maps schema names to the schema ids created by the `Operator`
+///
+/// The registry lives in memory unless a file is given with `OperatorBuilder::registry_path`,
+/// in which case every change is written back to that json file
+#[derive(Debug, Default)]
+pub struct SchemaRegistry {
+    path: Option<PathBuf>,
+    schemas: BTreeMap<String, RegisteredSchema>,
+}
+
+impl SchemaRegistry {
+    /// Creates a registry which is not persisted
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Loads the registry from a json file, the file is created on the first change if missing
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let schemas = if path.exists() {
+            let content = fs::read_to_string(&path).map_err(|source| Error::RegistryFile {
+                path: path.clone(),
+                source,
+            })?;
+            serde_json::from_str(&content)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            schemas,
+        })
+    }
+
+    /// Returns the schema recorded with the respective name
+    pub fn get(&self, name: &str) -> Option<&RegisteredSchema> {
+        self.schemas.get(name)
+    }
+
+    /// Returns the schema id recorded with the respective name
+    pub fn schema_id(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|schema| schema.schema_id.as_str())
+    }
+
+    /// Returns the schema id recorded with `name_or_id`, or `name_or_id` if it isn't a known name
+    pub fn resolve<'a>(&'a self, name_or_id: &'a str) -> &'a str {
+        self.schema_id(name_or_id).unwrap_or(name_or_id)
+    }
+
+    /// Iterates over the recorded schema names and schemas
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &RegisteredSchema)> {
+        self.schemas.iter()
+    }
+
+    /// Records a schema, bumping the version if the name already points to another schema id
+    pub fn insert(
+        &mut self,
+        name: &str,
+        schema_id: &str,
+        description: &str,
+        fields: &[StringTuple],
+    ) -> Result<()> {
+        let version = match self.schemas.get(name) {
+            Some(prev) if prev.schema_id == schema_id => prev.version,
+            Some(prev) => prev.version + 1,
+            None => 1,
+        };
+
+        let mut fields = fields.to_vec();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.schemas.insert(
+            name.to_string(),
+            RegisteredSchema {
+                schema_id: schema_id.to_string(),
+                version,
+                description: description.to_string(),
+                fields,
+            },
+        );
+
+        self.save()
+    }
+
+    /// Writes the registry to its file, does nothing for in-memory registries
+    pub fn save(&self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let content = serde_json::to_string_pretty(&self.schemas)?;
+
+        // Write to a temporary file first so a crash can't leave a half written registry
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|source| Error::RegistryFile {
+                path: path.clone(),
+                source,
+            })
+    }
+}
@@ -0,0 +1,145 @@
+//! Identities the `Operator` signs entries with
+//!
+//! `KeyPair` signs in process, `FileSigner` loads the key file for every signature and
+//! `SocketSigner` delegates to a signing daemon. Hardware or PKCS#11 modules plug in
+//! by implementing `Signer`
+#[cfg(unix)]
+mod socket;
+
+#[cfg(all(unix, any(test, feature = "testing")))]
+pub(crate) use socket::respond;
+#[cfg(unix)]
+pub use socket::SocketSigner;
+
+use crate::graphql::schemas::NextArguments;
+use crate::keystore::KeyStore;
+use crate::{Error, Result};
+
+use async_trait::async_trait;
+use bamboo_rs_core_ed25519_yasmf::entry::{is_lipmaa_required, MAX_ENTRY_SIZE};
+use bamboo_rs_core_ed25519_yasmf::{Entry as BambooEntry, Signature as BambooSignature};
+use p2panda_rs::entry::decode::decode_entry;
+use p2panda_rs::entry::error::EncodeEntryError;
+use p2panda_rs::entry::EncodedEntry;
+use p2panda_rs::identity::{KeyPair, PublicKey};
+use p2panda_rs::operation::EncodedOperation;
+
+/// Author of the entries published by the `Operator`
+///
+/// Signers only see the unsigned bamboo entry bytes, so the private key can live outside
+/// the process. `KeyPair` is the default signer
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Public key of the author, the entries are published to its logs
+    fn public_key(&self) -> PublicKey;
+
+    /// Signs the unsigned entry bytes, returning the 64 bytes ed25519 signature
+    async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>>;
+}
+
+#[async_trait]
+impl Signer for KeyPair {
+    fn public_key(&self) -> PublicKey {
+        KeyPair::public_key(self)
+    }
+
+    async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        Ok(KeyPair::sign(self, bytes).to_bytes().to_vec())
+    }
+}
+
+/// Signs with the key pair of a `KeyStore`, which is loaded for every signature
+///
+/// The private key is only in memory while signing, a stand-in for external signers in tests
+pub struct FileSigner {
+    key_store: KeyStore,
+    public_key: PublicKey,
+}
+
+impl FileSigner {
+    /// Loads the key pair once to learn its public key, use `KeyStore::load` or similar
+    pub fn new(key_store: KeyStore) -> Result<Self> {
+        let public_key = key_store.key_pair()?.public_key();
+        Ok(Self {
+            key_store,
+            public_key,
+        })
+    }
+}
+
+#[async_trait]
+impl Signer for FileSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let key_store = self.key_store.clone();
+        let key_pair = tokio::task::spawn_blocking(move || key_store.key_pair())
+            .await
+            .map_err(|err| Error::Signer(err.to_string()))??;
+
+        if key_pair.public_key() != self.public_key {
+            return Err(Error::Signer("the key file changed".to_string()));
+        }
+
+        Signer::sign(&key_pair, bytes).await
+    }
+}
+
+/// Size of an ed25519 signature
+const SIGNATURE_SIZE: usize = 64;
+
+/// Builds the entry of `payload` with the given arguments and signs it with `signer`
+///
+/// The signed entry is decoded again, so an invalid signature is caught before it reaches the node
+pub(crate) async fn sign_entry(
+    signer: &dyn Signer,
+    args: &NextArguments,
+    payload: &EncodedOperation,
+) -> Result<EncodedEntry> {
+    let payload_hash = payload.hash();
+
+    // Omit the skiplink when it's not required, like p2panda-rs does
+    let lipmaa_link = if is_lipmaa_required(args.seq_num.as_u64()) {
+        args.skiplink.as_ref().map(|link| link.into())
+    } else {
+        None
+    };
+
+    let mut entry: BambooEntry<_, &[u8]> = BambooEntry {
+        is_end_of_feed: false,
+        author: signer.public_key().into(),
+        log_id: args.log_id.as_u64(),
+        seq_num: args.seq_num.as_u64(),
+        lipmaa_link,
+        backlink: args.backlink.as_ref().map(|link| link.into()),
+        payload_size: payload.size(),
+        payload_hash: (&payload_hash).into(),
+        sig: None,
+    };
+
+    let mut unsigned = [0u8; MAX_ENTRY_SIZE];
+    let size = entry
+        .encode(&mut unsigned)
+        .map_err(EncodeEntryError::from)?;
+
+    let signature = signer.sign(&unsigned[..size]).await?;
+    if signature.len() != SIGNATURE_SIZE {
+        return Err(Error::Signer(format!(
+            "expected a signature of {} bytes, got {}",
+            SIGNATURE_SIZE,
+            signature.len()
+        )));
+    }
+    entry.sig = Some(BambooSignature(&signature[..]));
+
+    let mut signed = [0u8; MAX_ENTRY_SIZE];
+    let size = entry.encode(&mut signed).map_err(EncodeEntryError::from)?;
+    let encoded_entry = EncodedEntry::from_bytes(&signed[..size]);
+
+    decode_entry(&encoded_entry)
+        .map_err(|err| Error::Signer(format!("signed entry is invalid: {}", err)))?;
+
+    Ok(encoded_entry)
+}
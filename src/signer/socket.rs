@@ -0,0 +1,141 @@
+use crate::signer::Signer;
+use crate::{Error, Result};
+
+use async_trait::async_trait;
+use p2panda_rs::identity::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+/// Request sent to a signing daemon, one JSON object per line
+///
+/// `{"method":"public_key"}` or `{"method":"sign","bytes":"<hex>"}`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub(crate) enum SignerRequest {
+    PublicKey,
+    Sign { bytes: String },
+}
+
+/// Response of a signing daemon, one JSON object per line
+///
+/// `{"public_key":"<hex>"}`, `{"signature":"<hex>"}` or `{"error":"<message>"}`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct SignerResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Signs entries with a separate signing daemon listening on a Unix socket
+///
+/// Every request opens a new connection, writes a JSON line and reads the JSON line of the response:
+/// ```text
+/// -> {"method":"public_key"}
+/// <- {"public_key":"<hex>"}
+/// -> {"method":"sign","bytes":"<hex unsigned entry>"}
+/// <- {"signature":"<hex>"}
+/// ```
+/// Failures are answered with `{"error":"<message>"}`
+pub struct SocketSigner {
+    path: PathBuf,
+    public_key: PublicKey,
+}
+
+impl SocketSigner {
+    /// Connects to the daemon listening on `path` and asks it for the public key
+    pub async fn connect(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let response = request(&path, &SignerRequest::PublicKey).await?;
+
+        let public_key = response
+            .public_key
+            .ok_or_else(|| Error::Signer("daemon sent no public key".to_string()))?;
+        let public_key = PublicKey::new(&public_key)
+            .map_err(|err| Error::Signer(format!("daemon sent an invalid public key: {}", err)))?;
+
+        Ok(Self { path, public_key })
+    }
+}
+
+#[async_trait]
+impl Signer for SocketSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let bytes = hex::encode(bytes);
+        let response = request(&self.path, &SignerRequest::Sign { bytes }).await?;
+
+        let signature = response
+            .signature
+            .ok_or_else(|| Error::Signer("daemon sent no signature".to_string()))?;
+
+        hex::decode(signature)
+            .map_err(|err| Error::Signer(format!("daemon sent an invalid signature: {}", err)))
+    }
+}
+
+/// Answers a request of the socket protocol with `signer`
+#[cfg(any(test, feature = "testing"))]
+pub(crate) async fn respond(signer: &dyn Signer, line: &str) -> SignerResponse {
+    let request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return error_response(format!("invalid request: {}", err)),
+    };
+
+    match request {
+        SignerRequest::PublicKey => SignerResponse {
+            public_key: Some(signer.public_key().to_string()),
+            ..Default::default()
+        },
+        SignerRequest::Sign { bytes } => {
+            let bytes = match hex::decode(bytes) {
+                Ok(bytes) => bytes,
+                Err(err) => return error_response(format!("invalid bytes: {}", err)),
+            };
+
+            match signer.sign(&bytes).await {
+                Ok(signature) => SignerResponse {
+                    signature: Some(hex::encode(signature)),
+                    ..Default::default()
+                },
+                Err(err) => error_response(err.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+fn error_response(error: String) -> SignerResponse {
+    SignerResponse {
+        error: Some(error),
+        ..Default::default()
+    }
+}
+
+async fn request(path: &Path, request: &SignerRequest) -> Result<SignerResponse> {
+    let io_err = |err: std::io::Error| Error::Signer(format!("{}: {}", path.display(), err));
+
+    let mut stream = UnixStream::connect(path).await.map_err(io_err)?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await.map_err(io_err)?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .await
+        .map_err(io_err)?;
+
+    let response: SignerResponse = serde_json::from_str(&response)?;
+    match response.error {
+        Some(error) => Err(Error::Signer(error)),
+        None => Ok(response),
+    }
+}
@@ -0,0 +1,155 @@
+//! In-memory p2panda node to test an `Operator` without a running aquadoggo,
+//! and a signing daemon to test external signers
+//!
+//! Enabled with the `testing` feature:
+//!
+//! ```no_run
+//! # use zenode::{testing::MockNode, Error};
+//! # async fn run() -> Result<(), Error> {
+//! let node = MockNode::start().await.expect("Failed to start mock node");
+//! let op = node.operator()?;
+//! # Ok(())
+//! # }
+//! ```
+mod query;
+#[cfg(unix)]
+mod signer;
+mod state;
+
+#[cfg(unix)]
+pub use signer::SignerDaemon;
+
+use crate::{KeyStore, Operator, Result};
+use state::NodeState;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::convert::Infallible;
+use std::io;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::oneshot;
+
+#[derive(Deserialize)]
+struct GraphQLRequest {
+    query: String,
+    #[serde(default)]
+    variables: Value,
+}
+
+/// Serves the `nextArgs`, `publish`, `schema_definition_v1` and application schema queries
+/// of a p2panda node on a local port
+///
+/// Published entries and operations are decoded and verified with p2panda-rs, appended
+/// to the log of their author and materialised into documents. Everything lives in memory
+/// and the server stops when the `MockNode` is dropped
+pub struct MockNode {
+    endpoint: String,
+    state: Arc<Mutex<NodeState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockNode {
+    /// Starts the node on a random local port, must be called inside a tokio runtime
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(NodeState::default()));
+        let service_state = state.clone();
+
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let server = Server::from_tcp(listener)
+            .map_err(io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                signal.await.ok();
+            });
+
+        tokio::spawn(server);
+
+        Ok(Self {
+            endpoint: format!("http://{}/graphql", address),
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// GraphQL endpoint of the node
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Builds an `Operator` with a new in-memory key pair connected to this node
+    pub fn operator(&self) -> Result<Operator> {
+        Operator::builder()
+            .endpoint(&self.endpoint)
+            .key_store(KeyStore::generate())
+            .build()
+    }
+
+    /// Number of entries published to the node
+    pub fn entry_count(&self) -> usize {
+        self.state().entry_count()
+    }
+
+    fn state(&self) -> MutexGuard<'_, NodeState> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<NodeState>>,
+    req: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let request = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => serde_json::from_slice::<GraphQLRequest>(&body),
+        Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+    };
+
+    let request = match request {
+        Ok(request) => request,
+        Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+    };
+
+    let response = query::execute(&mut lock(&state), &request.query, &request.variables);
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(response.to_string()))
+        // Unwrap as the response is always valid
+        .unwrap())
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn lock(state: &Mutex<NodeState>) -> MutexGuard<'_, NodeState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
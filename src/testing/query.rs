@@ -0,0 +1,248 @@
+use crate::testing::state::{NodeState, View};
+
+use graphql_parser::query::{
+    parse_query, Definition, Field, OperationDefinition, Selection, SelectionSet, Value as Argument,
+};
+use p2panda_rs::operation::OperationValue;
+use p2panda_rs::schema::SchemaId;
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+
+type Selections<'a> = SelectionSet<'a, &'a str>;
+type QueryField<'a> = Field<'a, &'a str>;
+
+/// Executes a GraphQL request, returning the response body
+pub(crate) fn execute(state: &mut NodeState, query: &str, variables: &Value) -> Value {
+    match resolve(state, query, variables) {
+        Ok(data) => json!({ "data": data }),
+        Err(message) => json!({ "data": null, "errors": [{ "message": message }] }),
+    }
+}
+
+fn resolve(state: &mut NodeState, query: &str, variables: &Value) -> Result<Value, String> {
+    let document = parse_query::<&str>(query).map_err(|err| err.to_string())?;
+
+    let operation = document
+        .definitions
+        .iter()
+        .find_map(|definition| match definition {
+            Definition::Operation(operation) => Some(operation),
+            Definition::Fragment(_) => None,
+        })
+        .ok_or("no operation in the request")?;
+
+    let mut data = Map::new();
+
+    match operation {
+        OperationDefinition::SelectionSet(selection_set) => {
+            for field in fields(selection_set) {
+                data.insert(key(field), resolve_query(state, field, variables)?);
+            }
+        }
+        OperationDefinition::Query(query) => {
+            for field in fields(&query.selection_set) {
+                data.insert(key(field), resolve_query(state, field, variables)?);
+            }
+        }
+        OperationDefinition::Mutation(mutation) => {
+            for field in fields(&mutation.selection_set) {
+                data.insert(key(field), resolve_mutation(state, field, variables)?);
+            }
+        }
+        OperationDefinition::Subscription(_) => {
+            return Err("subscriptions are not supported".to_string())
+        }
+    }
+
+    Ok(Value::Object(data))
+}
+
+fn resolve_query(
+    state: &NodeState,
+    field: &QueryField,
+    variables: &Value,
+) -> Result<Value, String> {
+    if field.name == "nextArgs" {
+        let public_key =
+            argument(field, "publicKey", variables)?.ok_or("nextArgs expects a publicKey")?;
+        let view_id = argument(field, "viewId", variables)?;
+        let next_args = state.next_args(&public_key, view_id.as_deref())?;
+
+        return Ok(project(next_args.to_json(), &field.selection_set));
+    }
+
+    if let Some(schema_id) = field.name.strip_prefix("all_") {
+        let schema_id = known_schema(state, schema_id)?;
+
+        return Ok(Value::Array(
+            state
+                .documents(&schema_id)
+                .map(|view| project_document(state, view, &field.selection_set))
+                .collect(),
+        ));
+    }
+
+    let schema_id = known_schema(state, field.name)?;
+    let id = argument(field, "id", variables)?;
+    let view_id = argument(field, "viewId", variables)?;
+
+    let view = match (&id, &view_id) {
+        (_, Some(view_id)) => state
+            .view(view_id)
+            .filter(|view| id.as_ref().is_none_or(|id| &view.document_id == id)),
+        (Some(id), None) => state.document(id),
+        (None, None) => return Err(format!("{} expects an id or a viewId", field.name)),
+    };
+
+    Ok(view
+        .filter(|view| view.schema_id == schema_id)
+        .map_or(Value::Null, |view| {
+            project_document(state, view, &field.selection_set)
+        }))
+}
+
+fn resolve_mutation(
+    state: &mut NodeState,
+    field: &QueryField,
+    variables: &Value,
+) -> Result<Value, String> {
+    if field.name != "publish" {
+        return Err(format!("unknown mutation {}", field.name));
+    }
+
+    let entry = argument(field, "entry", variables)?.ok_or("publish expects an entry")?;
+    let operation =
+        argument(field, "operation", variables)?.ok_or("publish expects an operation")?;
+
+    let next_args = state.publish(&entry, &operation)?;
+    Ok(project(next_args.to_json(), &field.selection_set))
+}
+
+/// Parses the schema id of a query field, the schema has to be known by the node
+fn known_schema(state: &NodeState, schema_id: &str) -> Result<SchemaId, String> {
+    SchemaId::from_str(schema_id)
+        .ok()
+        .filter(|id| state.schema(id).is_ok())
+        .ok_or_else(|| format!("unknown field {} on type Query", schema_id))
+}
+
+fn project_document(state: &NodeState, view: &View, selection_set: &Selections) -> Value {
+    let mut object = Map::new();
+
+    for field in fields(selection_set) {
+        let value = match field.name {
+            "meta" => project(
+                json!({
+                    "documentId": view.document_id,
+                    "viewId": view.view_id.to_string(),
+                }),
+                &field.selection_set,
+            ),
+            "fields" => {
+                let mut values = Map::new();
+                for value_field in fields(&field.selection_set) {
+                    let value = view
+                        .fields
+                        .get(value_field.name)
+                        .map_or(Value::Null, |value| {
+                            project_value(state, value, &value_field.selection_set)
+                        });
+                    values.insert(key(value_field), value);
+                }
+                Value::Object(values)
+            }
+            _ => Value::Null,
+        };
+
+        object.insert(key(field), value);
+    }
+
+    Value::Object(object)
+}
+
+/// Relations are resolved to documents when the field has a selection, like the node does
+fn project_value(state: &NodeState, value: &OperationValue, selection_set: &Selections) -> Value {
+    let related = |id: String, view: Option<&View>| {
+        if selection_set.items.is_empty() {
+            Value::String(id)
+        } else {
+            view.map_or(Value::Null, |view| {
+                project_document(state, view, selection_set)
+            })
+        }
+    };
+
+    match value {
+        OperationValue::Boolean(value) => json!(value),
+        OperationValue::Integer(value) => json!(value),
+        OperationValue::Float(value) => json!(value),
+        OperationValue::String(value) => json!(value),
+        OperationValue::Relation(relation) => {
+            let id = relation.document_id().to_string();
+            related(id.clone(), state.document(&id))
+        }
+        OperationValue::RelationList(list) => list
+            .iter()
+            .map(|document_id| {
+                let id = document_id.to_string();
+                related(id.clone(), state.document(&id))
+            })
+            .collect(),
+        OperationValue::PinnedRelation(relation) => {
+            let id = relation.view_id().to_string();
+            related(id.clone(), state.view(&id))
+        }
+        OperationValue::PinnedRelationList(list) => list
+            .iter()
+            .map(|view_id| {
+                let id = view_id.to_string();
+                related(id.clone(), state.view(&id))
+            })
+            .collect(),
+    }
+}
+
+/// Keeps the keys of a json object which are part of the selection
+fn project(value: Value, selection_set: &Selections) -> Value {
+    match value {
+        Value::Object(mut object) if !selection_set.items.is_empty() => Value::Object(
+            fields(selection_set)
+                .map(|field| {
+                    let value = object.remove(field.name).unwrap_or(Value::Null);
+                    (key(field), project(value, &field.selection_set))
+                })
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn fields<'a, 'b>(selection_set: &'b Selections<'a>) -> impl Iterator<Item = &'b QueryField<'a>> {
+    selection_set.items.iter().filter_map(|item| match item {
+        Selection::Field(field) => Some(field),
+        _ => None,
+    })
+}
+
+fn key(field: &QueryField) -> String {
+    field.alias.unwrap_or(field.name).to_string()
+}
+
+/// Returns the value of a string argument, either given inline or as a variable
+fn argument(field: &QueryField, name: &str, variables: &Value) -> Result<Option<String>, String> {
+    let value = match field.arguments.iter().find(|(arg, _)| *arg == name) {
+        Some((_, value)) => value,
+        None => return Ok(None),
+    };
+
+    match value {
+        Argument::String(value) => Ok(Some(value.clone())),
+        Argument::Null => Ok(None),
+        Argument::Variable(variable) => match variables.get(variable) {
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            None | Some(Value::Null) => Ok(None),
+            Some(_) => Err(format!("variable {} must be a string", variable)),
+        },
+        _ => Err(format!("argument {} must be a string", name)),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::signer::{respond, Signer};
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::oneshot;
+
+static SOCKETS: AtomicUsize = AtomicUsize::new(0);
+
+/// Signing daemon answering the `SocketSigner` protocol with any `Signer`
+///
+/// Listens on a socket in the temp directory, which is removed when the daemon is dropped
+pub struct SignerDaemon {
+    path: PathBuf,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl SignerDaemon {
+    /// Starts the daemon, must be called inside a tokio runtime
+    pub async fn start(signer: impl Signer + 'static) -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "zenode_signer_{}_{}.sock",
+            std::process::id(),
+            SOCKETS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path)?;
+        let signer: Arc<dyn Signer> = Arc::new(signer);
+        let (shutdown, mut signal) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut signal => break,
+                    accepted = listener.accept() => {
+                        if let Ok((stream, _)) = accepted {
+                            tokio::spawn(serve(signer.clone(), stream));
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            path,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Path of the Unix socket
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SignerDaemon {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn serve(signer: Arc<dyn Signer>, stream: UnixStream) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = respond(signer.as_ref(), &line).await;
+        let mut response = serde_json::to_string(&response).map_err(io::Error::other)?;
+        response.push('\n');
+        writer.write_all(response.as_bytes()).await?;
+    }
+
+    Ok(())
+}
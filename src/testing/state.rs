@@ -0,0 +1,362 @@
+use p2panda_rs::document::DocumentViewId;
+use p2panda_rs::entry::decode::decode_entry;
+use p2panda_rs::entry::traits::{AsEncodedEntry, AsEntry};
+use p2panda_rs::entry::{EncodedEntry, Entry, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::PublicKey;
+use p2panda_rs::operation::decode::decode_operation;
+use p2panda_rs::operation::traits::{AsOperation, AsVerifiedOperation, Schematic};
+use p2panda_rs::operation::validate::validate_operation_with_entry;
+use p2panda_rs::operation::{EncodedOperation, OperationAction, OperationFields, OperationValue};
+use p2panda_rs::schema::{FieldType, Schema, SchemaId};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Arguments for the next entry of a log, returned by `nextArgs` and `publish`
+pub(crate) struct NextArgs {
+    log_id: u64,
+    seq_num: u64,
+    backlink: Option<Hash>,
+    skiplink: Option<Hash>,
+}
+
+impl NextArgs {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "logId": self.log_id.to_string(),
+            "seqNum": self.seq_num.to_string(),
+            "backlink": self.backlink.as_ref().map(|hash| hash.as_str()),
+            "skiplink": self.skiplink.as_ref().map(|hash| hash.as_str()),
+        })
+    }
+}
+
+/// Materialised view of a document
+#[derive(Clone, Debug)]
+pub(crate) struct View {
+    pub document_id: String,
+    pub view_id: DocumentViewId,
+    pub schema_id: SchemaId,
+    pub fields: OperationFields,
+    pub deleted: bool,
+}
+
+struct Log {
+    document_id: String,
+    entries: Vec<(Entry, Hash)>,
+}
+
+/// Logs and documents of the `MockNode`
+#[derive(Default)]
+pub(crate) struct NodeState {
+    /// Logs keyed by public key and log id
+    logs: HashMap<(String, u64), Log>,
+    /// Document id of every published operation
+    operations: HashMap<String, String>,
+    /// Latest view of every document, in the order they were created
+    documents: Vec<View>,
+    /// Every view of every document, keyed by view id
+    views: HashMap<String, View>,
+}
+
+impl NodeState {
+    /// Number of entries published to the node
+    pub fn entry_count(&self) -> usize {
+        self.logs.values().map(|log| log.entries.len()).sum()
+    }
+
+    /// Returns the latest view of a document, `None` if it doesn't exist or was deleted
+    pub fn document(&self, document_id: &str) -> Option<&View> {
+        self.documents
+            .iter()
+            .find(|view| view.document_id == document_id && !view.deleted)
+    }
+
+    /// Returns the view with the respective `view_id`
+    pub fn view(&self, view_id: &str) -> Option<&View> {
+        self.views.get(view_id)
+    }
+
+    /// Iterates over the latest views of the documents of a schema which weren't deleted
+    pub fn documents<'a>(&'a self, schema_id: &'a SchemaId) -> impl Iterator<Item = &'a View> {
+        self.documents
+            .iter()
+            .filter(move |view| &view.schema_id == schema_id && !view.deleted)
+    }
+
+    /// Returns the schema with the respective id, application schemas are built from
+    /// the materialised schema and field definitions
+    pub fn schema(&self, schema_id: &SchemaId) -> Result<Schema, String> {
+        let (name, view_id) = match schema_id {
+            SchemaId::Application(name, view_id) => (name, view_id),
+            system => return Schema::get_system(system.clone()).cloned().map_err(err),
+        };
+
+        let definition = self
+            .views
+            .get(&view_id.to_string())
+            .filter(|view| view.schema_id == SchemaId::SchemaDefinition(1))
+            .ok_or_else(|| format!("schema {} not found", schema_id))?;
+
+        if str_field(definition, "name")? != name {
+            return Err(format!("schema {} not found", schema_id));
+        }
+
+        let field_view_ids = match definition.fields.get("fields") {
+            Some(OperationValue::PinnedRelationList(list)) => list.document_view_ids(),
+            _ => return Err(format!("schema {} has no fields", schema_id)),
+        };
+
+        let mut fields = Vec::with_capacity(field_view_ids.len());
+
+        for field_view_id in field_view_ids {
+            let field = self
+                .views
+                .get(&field_view_id.to_string())
+                .filter(|view| view.schema_id == SchemaId::SchemaFieldDefinition(1))
+                .ok_or_else(|| format!("field definition {} not found", field_view_id))?;
+
+            let field_type = FieldType::from_str(str_field(field, "type")?).map_err(err)?;
+            fields.push((str_field(field, "name")?.to_string(), field_type));
+        }
+
+        Schema::new(schema_id, str_field(definition, "description")?, fields).map_err(err)
+    }
+
+    /// Returns the arguments for the next entry of `public_key`, in the log of the document
+    /// with the respective `view_id` or in a new log
+    pub fn next_args(&self, public_key: &str, view_id: Option<&str>) -> Result<NextArgs, String> {
+        let public_key = PublicKey::new(public_key).map_err(err)?.to_string();
+
+        let document_id = match view_id {
+            Some(view_id) => {
+                let view_id = DocumentViewId::from_str(view_id).map_err(err)?;
+                let document_id = self.document_of(&view_id)?;
+
+                if self.document(&document_id).is_none() {
+                    return Err(format!("document {} is deleted", document_id));
+                }
+
+                Some(document_id)
+            }
+            None => None,
+        };
+
+        let log = document_id.and_then(|document_id| {
+            self.logs
+                .iter()
+                .find(|((key, _), log)| key == &public_key && log.document_id == document_id)
+        });
+
+        Ok(match log {
+            Some(((_, log_id), log)) => log_args(*log_id, log),
+            None => NextArgs {
+                log_id: self.next_log_id(&public_key),
+                seq_num: 1,
+                backlink: None,
+                skiplink: None,
+            },
+        })
+    }
+
+    /// Decodes and verifies the entry and operation, appends the entry to its log
+    /// and materialises the document
+    pub fn publish(&mut self, entry: &str, operation: &str) -> Result<NextArgs, String> {
+        let encoded_entry = EncodedEntry::from_bytes(&hex::decode(entry).map_err(err)?);
+        let entry = decode_entry(&encoded_entry).map_err(err)?;
+
+        let encoded_operation = EncodedOperation::from_bytes(&hex::decode(operation).map_err(err)?);
+        let plain_operation = decode_operation(&encoded_operation).map_err(err)?;
+
+        let public_key = entry.public_key().to_string();
+        let log_id = entry.log_id().as_u64();
+        let log = self.logs.get(&(public_key.clone(), log_id));
+
+        let expected_seq_num = log.map_or(1, |log| log.entries.len() as u64 + 1);
+        if entry.seq_num().as_u64() != expected_seq_num {
+            return Err(format!(
+                "Entry's claimed seq num of {} does not match expected seq num of {} for given public key and log",
+                entry.seq_num().as_u64(),
+                expected_seq_num
+            ));
+        }
+
+        if log.is_none() && log_id != self.next_log_id(&public_key) {
+            return Err(format!(
+                "Entry's claimed log id of {} does not match expected next log id of {} for given public key",
+                log_id,
+                self.next_log_id(&public_key)
+            ));
+        }
+
+        let link = |seq_num: Option<SeqNum>| {
+            seq_num
+                .filter(|seq_num| seq_num.as_u64() > 0)
+                .and_then(|seq_num| log?.entries.get(seq_num.as_u64() as usize - 1))
+                .map(|(entry, hash)| (entry, hash))
+        };
+
+        let backlink = link(entry.seq_num().backlink_seq_num());
+        let skiplink = if entry.is_skiplink_required() {
+            link(entry.seq_num().skiplink_seq_num())
+        } else {
+            None
+        };
+
+        let schema = self.schema(plain_operation.schema_id())?;
+        let operation = validate_operation_with_entry(
+            &entry,
+            &encoded_entry,
+            skiplink,
+            backlink,
+            &plain_operation,
+            &encoded_operation,
+            &schema,
+        )
+        .map_err(err)?;
+
+        let operation_id = operation.id().clone();
+
+        let view = match operation.previous() {
+            None => View {
+                document_id: operation_id.to_string(),
+                view_id: DocumentViewId::new(std::slice::from_ref(&operation_id)),
+                schema_id: operation.schema_id(),
+                fields: operation.fields().unwrap_or_default(),
+                deleted: false,
+            },
+            Some(previous) => {
+                let document_id = self.document_of(&previous)?;
+                let mut view = self
+                    .document(&document_id)
+                    .ok_or_else(|| format!("document {} is deleted", document_id))?
+                    .clone();
+
+                if view.schema_id != operation.schema_id() {
+                    return Err(format!(
+                        "operation schema {} doesn't match document schema {}",
+                        operation.schema_id(),
+                        view.schema_id
+                    ));
+                }
+
+                let mut tips: Vec<_> = view
+                    .view_id
+                    .iter()
+                    .filter(|tip| !previous.graph_tips().contains(tip))
+                    .cloned()
+                    .collect();
+                tips.push(operation_id.clone());
+                view.view_id = DocumentViewId::new(&tips);
+
+                match operation.action() {
+                    OperationAction::Delete => view.deleted = true,
+                    _ => {
+                        for (name, value) in operation.fields().unwrap_or_default().iter() {
+                            view.fields.update(name, value.clone()).map_err(err)?;
+                        }
+                    }
+                }
+
+                view
+            }
+        };
+
+        if let Some(log) = log {
+            if log.document_id != view.document_id {
+                return Err(format!(
+                    "log {} belongs to document {}",
+                    log_id, log.document_id
+                ));
+            }
+        }
+
+        let hash = encoded_entry.hash();
+        let log = self
+            .logs
+            .entry((public_key, log_id))
+            .or_insert_with(|| Log {
+                document_id: view.document_id.clone(),
+                entries: Vec::new(),
+            });
+        log.entries.push((entry, hash));
+        let next_args = log_args(log_id, log);
+
+        self.operations
+            .insert(operation_id.to_string(), view.document_id.clone());
+        self.views.insert(view.view_id.to_string(), view.clone());
+
+        match self
+            .documents
+            .iter_mut()
+            .find(|document| document.document_id == view.document_id)
+        {
+            Some(document) => *document = view,
+            None => self.documents.push(view),
+        }
+
+        Ok(next_args)
+    }
+
+    /// Returns the document all the operations of the view id belong to
+    fn document_of(&self, view_id: &DocumentViewId) -> Result<String, String> {
+        let mut document_ids = view_id.iter().map(|operation_id| {
+            self.operations
+                .get(operation_id.as_str())
+                .ok_or_else(|| format!("operation {} not found", operation_id))
+        });
+
+        // Unwrap as view ids contain at least one operation id
+        let document_id = document_ids.next().unwrap()?;
+
+        for other in document_ids {
+            if other? != document_id {
+                return Err(format!("view id {} spans multiple documents", view_id));
+            }
+        }
+
+        Ok(document_id.clone())
+    }
+
+    fn next_log_id(&self, public_key: &str) -> u64 {
+        self.logs
+            .keys()
+            .filter(|(key, _)| key == public_key)
+            .map(|(_, log_id)| log_id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn log_args(log_id: u64, log: &Log) -> NextArgs {
+    let seq_num = log.entries.len() as u64 + 1;
+    // Unwrap as the seq num is never zero
+    let next = SeqNum::new(seq_num).unwrap();
+
+    let skiplink = next
+        .skiplink_seq_num()
+        .filter(|skiplink| Some(*skiplink) != next.backlink_seq_num() && skiplink.as_u64() > 0)
+        .map(|skiplink| log.entries[skiplink.as_u64() as usize - 1].1.clone());
+
+    NextArgs {
+        log_id,
+        seq_num,
+        backlink: log.entries.last().map(|(_, hash)| hash.clone()),
+        skiplink,
+    }
+}
+
+fn str_field<'a>(view: &'a View, name: &str) -> Result<&'a str, String> {
+    match view.fields.get(name) {
+        Some(OperationValue::String(value)) => Ok(value),
+        _ => Err(format!(
+            "document {} has no {} field",
+            view.document_id, name
+        )),
+    }
+}
+
+fn err(err: impl ToString) -> String {
+    err.to_string()
+}
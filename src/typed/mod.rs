@@ -0,0 +1,249 @@
+use crate::builder::fields::{FieldType, FieldValue};
+use crate::operator::{FieldTuple, StringTuple};
+use crate::{Error, Result};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::marker::PhantomData;
+
+#[cfg(feature = "derive")]
+pub use zenode_derive::Schema;
+
+/// Resolves the schema name of a relation target to its schema id
+pub type SchemaIdResolver<'a> = &'a dyn Fn(&str) -> Result<String>;
+
+/// Rust struct mapped to a p2panda schema, usually implemented with `#[derive(Schema)]`
+///
+/// #### Example
+/// ```ignore
+/// #[derive(Schema, Serialize, Deserialize)]
+/// #[schema(name = "pet", description = "Pet schema")]
+/// struct Pet {
+///     name: String,
+///     age: i64,
+///     owner: Relation<Person>,
+/// }
+/// ```
+pub trait Schema: DeserializeOwned {
+    /// Name of the schema
+    const NAME: &'static str;
+
+    /// Description of the schema
+    const DESCRIPTION: &'static str;
+
+    /// Field definitions of the schema, in the shape expected by `Operator::create_schema`
+    fn field_defs(schema_id: SchemaIdResolver) -> Result<Vec<StringTuple>>;
+
+    /// Field values of this instance, in the shape expected by `Operator::create_instance`
+    fn fields(&self) -> Vec<FieldTuple>;
+}
+
+/// Rust type that can be used as the type of a `Schema` field
+pub trait SchemaField {
+    /// `FieldType` of the field as string, relation targets are resolved with `schema_id`
+    fn field_type(schema_id: SchemaIdResolver) -> Result<String>;
+
+    /// Value of the field
+    fn field_value(&self) -> FieldValue;
+}
+
+macro_rules! impl_schema_field {
+    ($t:ty, $field_type:expr) => {
+        impl SchemaField for $t {
+            fn field_type(_: SchemaIdResolver) -> Result<String> {
+                Ok($field_type.to_string())
+            }
+
+            fn field_value(&self) -> FieldValue {
+                self.clone().into()
+            }
+        }
+    };
+}
+
+impl_schema_field!(bool, FieldType::Bool);
+impl_schema_field!(i32, FieldType::Int);
+impl_schema_field!(u32, FieldType::Int);
+impl_schema_field!(i64, FieldType::Int);
+impl_schema_field!(f32, FieldType::Float);
+impl_schema_field!(f64, FieldType::Float);
+impl_schema_field!(String, FieldType::Str);
+
+/// Relation to a document of the schema `T`
+pub struct Relation<T> {
+    pub document_id: String,
+    target: PhantomData<T>,
+}
+
+/// Relation to a list of documents of the schema `T`
+pub struct RelationList<T> {
+    pub document_ids: Vec<String>,
+    target: PhantomData<T>,
+}
+
+/// Relation to a document view of the schema `T`
+pub struct PinnedRelation<T> {
+    pub view_id: String,
+    target: PhantomData<T>,
+}
+
+/// Relation to a list of document views of the schema `T`
+pub struct PinnedRelationList<T> {
+    pub view_ids: Vec<String>,
+    target: PhantomData<T>,
+}
+
+impl<T> Relation<T> {
+    pub fn new(document_id: &str) -> Self {
+        Self {
+            document_id: document_id.to_string(),
+            target: PhantomData,
+        }
+    }
+}
+
+impl<T> RelationList<T> {
+    pub fn new(document_ids: &[&str]) -> Self {
+        Self {
+            document_ids: document_ids.iter().map(|id| id.to_string()).collect(),
+            target: PhantomData,
+        }
+    }
+}
+
+impl<T> PinnedRelation<T> {
+    pub fn new(view_id: &str) -> Self {
+        Self {
+            view_id: view_id.to_string(),
+            target: PhantomData,
+        }
+    }
+}
+
+impl<T> PinnedRelationList<T> {
+    pub fn new(view_ids: &[&str]) -> Self {
+        Self {
+            view_ids: view_ids.iter().map(|id| id.to_string()).collect(),
+            target: PhantomData,
+        }
+    }
+}
+
+/// Splits a view id in the shape `<operation_id>_<operation_id>` into operation ids
+fn operation_ids(view_id: &str) -> Vec<String> {
+    view_id.split('_').map(|id| id.to_string()).collect()
+}
+
+/// Resolves the schema id of `T` and formats the relation field type
+fn relation_type<T: Schema>(
+    schema_id: SchemaIdResolver,
+    field_type: fn(&str) -> String,
+) -> Result<String> {
+    let id = schema_id(T::NAME)?;
+    Ok(field_type(&id))
+}
+
+impl<T: Schema> SchemaField for Relation<T> {
+    fn field_type(schema_id: SchemaIdResolver) -> Result<String> {
+        relation_type::<T>(schema_id, |id| FieldType::Relation(id).to_string())
+    }
+
+    fn field_value(&self) -> FieldValue {
+        FieldValue::Relation(self.document_id.clone())
+    }
+}
+
+impl<T: Schema> SchemaField for RelationList<T> {
+    fn field_type(schema_id: SchemaIdResolver) -> Result<String> {
+        relation_type::<T>(schema_id, |id| FieldType::RelationList(id).to_string())
+    }
+
+    fn field_value(&self) -> FieldValue {
+        FieldValue::RelationList(self.document_ids.clone())
+    }
+}
+
+impl<T: Schema> SchemaField for PinnedRelation<T> {
+    fn field_type(schema_id: SchemaIdResolver) -> Result<String> {
+        relation_type::<T>(schema_id, |id| FieldType::PinnedRelation(id).to_string())
+    }
+
+    fn field_value(&self) -> FieldValue {
+        FieldValue::PinnedRelation(operation_ids(&self.view_id))
+    }
+}
+
+impl<T: Schema> SchemaField for PinnedRelationList<T> {
+    fn field_type(schema_id: SchemaIdResolver) -> Result<String> {
+        relation_type::<T>(schema_id, |id| {
+            FieldType::PinnedRelationList(id).to_string()
+        })
+    }
+
+    fn field_value(&self) -> FieldValue {
+        FieldValue::PinnedRelationList(self.view_ids.iter().map(|id| operation_ids(id)).collect())
+    }
+}
+
+// The relation wrappers are (de)serialized as the plain ids returned by `Operator::get_document`
+// ------------------------------------------------
+
+macro_rules! impl_relation_serde {
+    ($wrapper:ident, $field:ident, $t:ty) => {
+        impl<T> Serialize for $wrapper<T> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.$field.serialize(serializer)
+            }
+        }
+
+        impl<'de, T> Deserialize<'de> for $wrapper<T> {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(Self {
+                    $field: <$t>::deserialize(deserializer)?,
+                    target: PhantomData,
+                })
+            }
+        }
+
+        impl<T> Clone for $wrapper<T> {
+            fn clone(&self) -> Self {
+                Self {
+                    $field: self.$field.clone(),
+                    target: PhantomData,
+                }
+            }
+        }
+
+        impl<T> std::fmt::Debug for $wrapper<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($wrapper))
+                    .field(&self.$field)
+                    .finish()
+            }
+        }
+
+        impl<T> PartialEq for $wrapper<T> {
+            fn eq(&self, other: &Self) -> bool {
+                self.$field == other.$field
+            }
+        }
+    };
+}
+
+impl_relation_serde!(Relation, document_id, String);
+impl_relation_serde!(RelationList, document_ids, Vec<String>);
+impl_relation_serde!(PinnedRelation, view_id, String);
+impl_relation_serde!(PinnedRelationList, view_ids, Vec<String>);
+
+/// Builds a `SchemaIdResolver` from `(schema name, schema id)` pairs
+pub fn resolve_from<'a>(
+    relations: &'a [(&'a str, &'a str)],
+) -> impl Fn(&str) -> Result<String> + 'a {
+    move |name| {
+        relations
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, id)| id.to_string())
+            .ok_or_else(|| Error::SchemaNotFound(name.to_string()))
+    }
+}
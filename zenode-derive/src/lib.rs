@@ -0,0 +1,203 @@
+//! `#[derive(Schema)]` for [zenode](https://github.com/Gers2017/zenode)
+//!
+//! Maps a struct with named fields to a p2panda schema, every field type must implement
+//! `zenode::typed::SchemaField` (`bool`, integers, floats, `String` and the relation wrappers)
+//!
+//! ```ignore
+//! #[derive(Schema, Serialize, Deserialize)]
+//! #[schema(name = "pokemon", description = "Pokemon schema")]
+//! struct Pokemon {
+//!     pokemon_id: i64,
+//!     pokemon_name: String,
+//! }
+//! ```
+//!
+//! The schema field of a struct field is its name without the `r#` prefix, or the name given
+//! with `#[serde(rename = "...")]`. `#[serde(rename_all = "...")]` and split renames are rejected,
+//! the field names of the schema have to match the names serde deserializes the document with
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::{
+    parse_macro_input, token, Attribute, Data, DeriveInput, Expr, Field, Fields, LitStr, Token,
+};
+
+#[proc_macro_derive(Schema, attributes(schema))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Schema can't be derived for generic structs",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "Schema can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Schema can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut name = LitStr::new(&to_snake_case(&ident.to_string()), Span::call_site());
+    let mut description = LitStr::new(&ident.to_string(), Span::call_site());
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("schema")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = meta.value()?.parse()?;
+                Ok(())
+            } else if meta.path.is_ident("description") {
+                description = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `name` or `description`"))
+            }
+        })?;
+    }
+
+    reject_rename_all(&input.attrs)?;
+
+    let field_names = fields
+        .iter()
+        .map(field_name)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let field_defs = fields.iter().zip(&field_names).map(|(f, field_name)| {
+        let ty = &f.ty;
+
+        quote! {
+            (
+                ::std::string::String::from(#field_name),
+                <#ty as ::zenode::typed::SchemaField>::field_type(schema_id)?,
+            )
+        }
+    });
+
+    let field_values = fields.iter().zip(&field_names).map(|(f, field_name)| {
+        let field_ident = f.ident.as_ref().unwrap();
+
+        quote! {
+            (
+                ::std::string::String::from(#field_name),
+                ::zenode::typed::SchemaField::field_value(&self.#field_ident),
+            )
+        }
+    });
+
+    Ok(quote! {
+        impl ::zenode::typed::Schema for #ident {
+            const NAME: &'static str = #name;
+            const DESCRIPTION: &'static str = #description;
+
+            fn field_defs(
+                schema_id: ::zenode::typed::SchemaIdResolver,
+            ) -> ::zenode::Result<::std::vec::Vec<::zenode::StringTuple>> {
+                ::std::result::Result::Ok(::std::vec![#(#field_defs),*])
+            }
+
+            fn fields(&self) -> ::std::vec::Vec<::zenode::FieldTuple> {
+                ::std::vec![#(#field_values),*]
+            }
+        }
+    })
+}
+
+/// Name of the schema field, `#[serde(rename = "...")]` or the ident without `r#`
+fn field_name(field: &Field) -> syn::Result<String> {
+    let ident = field.ident.as_ref().unwrap();
+    let mut name = ident.unraw().to_string();
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                if !meta.input.peek(Token![=]) {
+                    return Err(meta.error(
+                        "Schema doesn't support different names to serialize and deserialize",
+                    ));
+                }
+                name = meta.value()?.parse::<LitStr>()?.value();
+            }
+            skip_meta(&meta)
+        })?;
+    }
+
+    Ok(name)
+}
+
+/// `#[serde(rename_all = "...")]` would change the field names serde expects in the document
+fn reject_rename_all(attrs: &[Attribute]) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                return Err(meta.error(
+                    "Schema doesn't support `rename_all`, rename the fields with `#[serde(rename = \"...\")]`",
+                ));
+            }
+            skip_meta(&meta)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Consumes the rest of a serde attribute item that isn't relevant for the schema
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(token::Paren) {
+        meta.parse_nested_meta(|nested| skip_meta(&nested))?;
+    }
+    Ok(())
+}
+
+/// `PokemonTrainer` -> `pokemon_trainer`, `HTTPServer` -> `http_server`
+fn to_snake_case(ident: &str) -> String {
+    let chars: Vec<char> = ident.chars().collect();
+    let mut name = String::with_capacity(ident.len());
+
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1);
+
+            // A word starts after a lowercase letter or at the last capital of an acronym
+            let starts_word = match prev {
+                Some(prev) if prev.is_uppercase() => next.is_some_and(|n| n.is_lowercase()),
+                Some(prev) => prev != '_',
+                None => false,
+            };
+            if starts_word {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(*c);
+        }
+    }
+
+    name
+}
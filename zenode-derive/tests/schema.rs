@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use zenode::typed::{self, PinnedRelationList, Relation, Schema};
+use zenode::{Document, FieldValue};
+
+#[derive(Schema, Serialize, Deserialize, Debug, PartialEq)]
+#[schema(description = "Pokemon trainer schema")]
+struct PokemonTrainer {
+    name: String,
+}
+
+#[derive(Schema, Serialize, Deserialize, Debug, PartialEq)]
+#[schema(name = "pokemon", description = "Pokemon schema")]
+struct Pokemon {
+    pokemon_id: i64,
+    pokemon_name: String,
+    weight: f64,
+    shiny: bool,
+    trainer: Relation<PokemonTrainer>,
+    rivals: PinnedRelationList<PokemonTrainer>,
+}
+
+#[derive(Schema, Serialize, Deserialize, Debug, PartialEq)]
+struct HTTPServerConfig {
+    r#type: String,
+    #[serde(rename = "port_number", default)]
+    port: i64,
+}
+
+#[test]
+fn derive_schema_name_and_description() {
+    assert_eq!(PokemonTrainer::NAME, "pokemon_trainer");
+    assert_eq!(PokemonTrainer::DESCRIPTION, "Pokemon trainer schema");
+    assert_eq!(Pokemon::NAME, "pokemon");
+    assert_eq!(HTTPServerConfig::NAME, "http_server_config");
+}
+
+#[test]
+fn derive_schema_raw_and_renamed_fields() {
+    let defs = HTTPServerConfig::field_defs(&typed::resolve_from(&[])).unwrap();
+    assert_eq!(defs[0], ("type".to_string(), "str".to_string()));
+    assert_eq!(defs[1], ("port_number".to_string(), "int".to_string()));
+
+    let config = HTTPServerConfig {
+        r#type: "http".to_string(),
+        port: 2020,
+    };
+    let fields = config.fields();
+    assert_eq!(fields[0].0, "type");
+    assert_eq!(
+        fields[1],
+        ("port_number".to_string(), FieldValue::Int(2020))
+    );
+
+    // the document fields are deserialized with the same names
+    let json = r#"{
+        "meta": { "documentId": "0020dd", "viewId": "0020dd" },
+        "fields": { "type": "http", "port_number": 2020 }
+    }"#;
+    let doc: Document<HTTPServerConfig> = serde_json::from_str(json).unwrap();
+    assert_eq!(doc.fields, config);
+}
+
+#[test]
+fn derive_schema_field_defs() {
+    let relations = [("pokemon_trainer", "pokemon_trainer_0020aa")];
+    let defs = Pokemon::field_defs(&typed::resolve_from(&relations)).unwrap();
+
+    let expected = [
+        ("pokemon_id", "int"),
+        ("pokemon_name", "str"),
+        ("weight", "float"),
+        ("shiny", "bool"),
+        ("trainer", "relation(pokemon_trainer_0020aa)"),
+        ("rivals", "pinned_relation_list(pokemon_trainer_0020aa)"),
+    ];
+
+    for ((name, field_type), (expected_name, expected_type)) in defs.iter().zip(expected) {
+        assert_eq!(name, expected_name);
+        assert_eq!(field_type, expected_type);
+    }
+
+    // relation targets must be resolved
+    assert!(Pokemon::field_defs(&typed::resolve_from(&[])).is_err());
+}
+
+#[test]
+fn derive_schema_fields() {
+    let pokemon = Pokemon {
+        pokemon_id: 1,
+        pokemon_name: "true".to_string(),
+        weight: 6.9,
+        shiny: false,
+        trainer: Relation::new("0020aa"),
+        rivals: PinnedRelationList::new(&["0020bb_0020cc"]),
+    };
+
+    let fields = pokemon.fields();
+
+    assert_eq!(fields[0].1, FieldValue::Int(1));
+    assert_eq!(fields[1].1, FieldValue::Str("true".to_string()));
+    assert_eq!(fields[2].1, FieldValue::Float(6.9));
+    assert_eq!(fields[3].1, FieldValue::Bool(false));
+    assert_eq!(fields[4].1, FieldValue::Relation("0020aa".to_string()));
+    assert_eq!(
+        fields[5].1,
+        FieldValue::PinnedRelationList(vec![vec!["0020bb".to_string(), "0020cc".to_string()]])
+    );
+}
+
+#[test]
+fn derive_schema_from_document() {
+    let json = r#"{
+        "meta": { "documentId": "0020dd", "viewId": "0020dd" },
+        "fields": {
+            "pokemon_id": 25,
+            "pokemon_name": "Pikachu",
+            "weight": 6.0,
+            "shiny": true,
+            "trainer": "0020aa",
+            "rivals": ["0020bb"]
+        }
+    }"#;
+
+    let doc: Document<Pokemon> = serde_json::from_str(json).unwrap();
+
+    assert_eq!(doc.fields.pokemon_name, "Pikachu");
+    assert_eq!(doc.fields.trainer, Relation::new("0020aa"));
+    assert_eq!(doc.fields.rivals, PinnedRelationList::new(&["0020bb"]));
+}